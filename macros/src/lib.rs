@@ -0,0 +1,54 @@
+//! `props-rs-macros` parses `.properties` files at compile time with
+//! [`props-rs`](https://docs.rs/props-rs), for embedding known-good
+//! configuration into a binary.
+//!
+//! ```
+//! use props_rs_macros::props;
+//!
+//! const DEFAULTS: &[(&str, &str)] = props!("host=localhost\nport=8080");
+//! assert_eq!(("port", "8080"), DEFAULTS[1]);
+//! ```
+#![deny(missing_docs)]
+#![deny(rustdoc::missing_crate_level_docs)]
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, LitStr};
+
+/// Parses a string literal holding a properties file, expanding to a
+/// `&'static [(&'static str, &'static str)]` of its keys and values in order.
+///
+/// Invalid input fails to compile, with the position of the error. Like the
+/// parser, the literal may only contain ISO 8859-1 characters.
+///
+/// ```compile_fail
+/// use props_rs_macros::props;
+///
+/// let p = props!("=missing key");
+/// ```
+#[proc_macro]
+pub fn props(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    let text = lit.value();
+    let error = |msg: String| syn::Error::new(lit.span(), msg).to_compile_error().into();
+    // the parser reads one character per byte, as ISO 8859-1
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        if (c as u32) > 0xFF {
+            return error(format!("`{}` is not an ISO 8859-1 character", c));
+        }
+        bytes.push(c as u8);
+    }
+    let props = match props_rs::parse(&bytes) {
+        Ok(props) => props,
+        Err(e) => return error(format!("invalid properties: {}", e)),
+    };
+    let keys = props.iter().map(|p| &p.key);
+    let values = props.iter().map(|p| &p.value);
+    let expanded = quote! {
+        {
+            const PROPS: &[(&str, &str)] = &[#((#keys, #values)),*];
+            PROPS
+        }
+    };
+    expanded.into()
+}
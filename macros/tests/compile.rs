@@ -0,0 +1,6 @@
+#[test]
+fn test_props() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass.rs");
+    t.compile_fail("tests/ui/invalid.rs");
+}
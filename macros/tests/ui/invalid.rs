@@ -0,0 +1,6 @@
+use props_rs_macros::props;
+
+fn main() {
+    let _ = props!("k=v\n=missing key");
+    let _ = props!("k=\u{20ac}");
+}
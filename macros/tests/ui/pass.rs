@@ -0,0 +1,12 @@
+use props_rs_macros::props;
+
+const EMPTY: &[(&str, &str)] = props!("# only a comment\n");
+
+fn main() {
+    let p = props!("k=v\nkey\\ two = caf\u{e9} \\\n    continued\nk=again");
+    assert_eq!(
+        &[("k", "v"), ("key two", "caf\u{e9} continued"), ("k", "again")],
+        p
+    );
+    assert!(EMPTY.is_empty());
+}
@@ -0,0 +1,60 @@
+//! Parsing of embedded input which is only done once per process
+use crate::{parse_or_panic, Property};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+/// The properties parsed from each input, keyed by the address and length of
+/// the input
+type Cache = Mutex<HashMap<(usize, usize), &'static [Property]>>;
+
+static CACHE: OnceLock<Cache> = OnceLock::new();
+
+/// Parses input embedded in the binary, such as with [`include_bytes!`], the
+/// first time it is called with that input. Later calls return the same
+/// properties without parsing again.
+///
+/// Inputs are identified by their address and length rather than their
+/// contents, and the parsed properties live for the rest of the process.
+///
+/// ```
+/// use props_rs::*;
+///
+/// static DEFAULTS: &[u8] = b"host=localhost\nport=8080";
+/// let props = parse_cached(DEFAULTS);
+/// assert_eq!("8080", props[1].value);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the input is not a valid properties file, as [`parse_or_panic`]
+/// does.
+pub fn parse_cached(input: &'static [u8]) -> &'static [Property] {
+    let key = (input.as_ptr() as usize, input.len());
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some(props) = cache.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+        return props;
+    }
+    // parse without holding the lock, a racing thread may parse it as well
+    let parsed: &'static [Property] = Box::leak(parse_or_panic(input).into_boxed_slice());
+    let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+    cache.entry(key).or_insert(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cached() {
+        static INPUT: &[u8] = b"a=1\nb=2";
+        let first = parse_cached(INPUT);
+        let second = parse_cached(INPUT);
+        assert_eq!(2, first.len());
+        assert!(std::ptr::eq(first, second));
+
+        // a different slice of the same bytes is parsed separately
+        let prefix = parse_cached(&INPUT[..3]);
+        assert_eq!(1, prefix.len());
+        assert!(!std::ptr::eq(first, prefix));
+    }
+}
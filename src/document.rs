@@ -0,0 +1,431 @@
+//! A lossless model of a properties file which retains comments and blank
+//! lines so that it can be written back out unchanged.
+use crate::parser::{self, Line, ParseOptions};
+use crate::writer;
+use crate::WriteOptions;
+use crate::{ParseError, Property};
+use std::collections::HashMap;
+
+/// A comment line such as `# note`, split into its parts so that it is
+/// written back exactly as it was read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comment {
+    /// Any whitespace preceding the comment marker
+    pub indent: String,
+    /// The comment marker, either `#` or `!`
+    pub marker: char,
+    /// The text following the marker, up to the end of the line
+    pub text: String,
+    /// The line ending of the comment. Empty if it was the last line in the file
+    pub eol: String,
+}
+
+/// A single entry in a [`Document`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Entry {
+    /// A line containing only whitespace, stored verbatim including its
+    /// line ending
+    Blank(String),
+    /// A comment line
+    Comment(Comment),
+    /// A key-value pair which may span several physical lines
+    Property {
+        /// The parsed property
+        property: Property,
+        /// The source text the property was parsed from. When present it is
+        /// written verbatim, so it should be cleared after editing `property`
+        raw: Option<String>,
+        /// The comment following the value on its last line, starting from its
+        /// marker e.g. `# note`. Only populated when parsing with
+        /// [`ParseOptions::inline_comments`]
+        trailing_comment: Option<String>,
+    },
+}
+
+/// A parsed properties file which retains comments, blank lines and the
+/// original formatting of every line.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Document {
+    /// The entries of the file, in order
+    pub entries: Vec<Entry>,
+}
+
+impl Document {
+    /// Returns an iterator over the properties in the document, in order
+    pub fn properties(&self) -> impl Iterator<Item = &Property> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Property { property, .. } => Some(property),
+            _ => None,
+        })
+    }
+
+    /// Serializes the document. Entries which were parsed and not modified
+    /// are written exactly as they were read.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = String::new();
+        for entry in self.entries.iter() {
+            match entry {
+                Entry::Blank(raw) => out.push_str(raw),
+                Entry::Comment(c) => {
+                    out.push_str(&c.indent);
+                    out.push(c.marker);
+                    out.push_str(&c.text);
+                    out.push_str(&c.eol);
+                }
+                Entry::Property { raw: Some(raw), .. } => out.push_str(raw),
+                Entry::Property {
+                    property,
+                    raw: None,
+                    trailing_comment,
+                } => {
+                    match trailing_comment {
+                        Some(comment) => {
+                            writer::write_property_with_comment(property, comment, &mut out)
+                        }
+                        None => writer::write_property(property, &mut out),
+                    }
+                    out.push('\n');
+                }
+            }
+        }
+        writer::encode(&out, &WriteOptions::default())
+    }
+}
+
+/// Decodes source bytes the same way the parser does, one character per byte
+fn decode(raw: &[u8]) -> String {
+    raw.iter().map(|&b| b as char).collect()
+}
+
+/// Splits the source text of a comment line into its parts
+fn comment(raw: &str) -> Comment {
+    // the parser guarantees a comment begins with a marker after its indent,
+    // which can not hold a marker itself
+    let (indent, body) = raw.split_at(raw.find(['#', '!']).unwrap_or(0));
+    let mut chars = body.chars();
+    let marker = chars.next().unwrap_or('#');
+    let rest = chars.as_str();
+    let text = rest.trim_end_matches(['\r', '\n']);
+    Comment {
+        indent: indent.to_string(),
+        marker,
+        text: text.to_string(),
+        eol: rest[text.len()..].to_string(),
+    }
+}
+
+/// Parses a properties file into a [`Document`] which retains comments and
+/// blank lines alongside the properties.
+///
+/// Parsing is lossless, so every occurrence of a duplicated key is kept as its
+/// own [`Entry::Property`]. Use [`resolve_duplicates`] to collapse them.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let input = b"# settings\n    # indented\nkey = value\n";
+/// let doc = parse_with_comments(input).unwrap();
+/// assert_eq!(1, doc.properties().count());
+/// assert_eq!(input.to_vec(), doc.to_bytes());
+/// ```
+pub fn parse_with_comments(input: &[u8]) -> Result<Document, ParseError> {
+    parse_with_comments_and_options(input, &mut ParseOptions::default())
+}
+
+/// Parses a properties file into a [`Document`] like [`parse_with_comments`],
+/// according to the given options.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let mut opts = ParseOptions {
+///     inline_comments: true,
+///     ..Default::default()
+/// };
+/// let input = b"key = value # note\n";
+/// let doc = parse_with_comments_and_options(input, &mut opts).unwrap();
+/// assert_eq!("value", doc.properties().next().unwrap().value);
+/// assert_eq!(input.to_vec(), doc.to_bytes());
+/// ```
+pub fn parse_with_comments_and_options(
+    input: &[u8],
+    opts: &mut ParseOptions,
+) -> Result<Document, ParseError> {
+    let lines = parser::lines(input, opts)?;
+    let entries = lines
+        .into_iter()
+        .map(|line| match line {
+            Line::Blank(raw) => Entry::Blank(decode(raw)),
+            Line::Comment(raw) => Entry::Comment(comment(&decode(raw))),
+            Line::Property(property, trailing_comment, raw) => Entry::Property {
+                property,
+                raw: Some(decode(raw)),
+                trailing_comment,
+            },
+        })
+        .collect();
+    Ok(Document { entries })
+}
+
+/// Which occurrence of a duplicated key [`resolve_duplicates`] keeps.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Keep the last occurrence, matching the value [`to_map`](crate::to_map)
+    /// and [`Properties`](crate::Properties) use
+    #[default]
+    KeepLast,
+    /// Keep the first occurrence
+    KeepFirst,
+}
+
+/// Collapses every duplicated key in `entries` to a single
+/// [`Entry::Property`], chosen by `policy`. The kept occurrence stays where it
+/// was, and comments and blank lines are left untouched.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let doc = parse_with_comments(b"a=1\n# note\na=2\n").unwrap();
+/// let entries = resolve_duplicates(doc.entries, DuplicatePolicy::KeepFirst);
+/// let doc = Document { entries };
+/// assert_eq!(b"a=1\n# note\n".to_vec(), doc.to_bytes());
+/// ```
+pub fn resolve_duplicates(entries: Vec<Entry>, policy: DuplicatePolicy) -> Vec<Entry> {
+    // the index of the occurrence to keep for each key
+    let mut kept: HashMap<String, usize> = HashMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if let Entry::Property { property, .. } = entry {
+            let k = kept.entry(property.key.clone()).or_insert(i);
+            if policy == DuplicatePolicy::KeepLast {
+                *k = i;
+            }
+        }
+    }
+    entries
+        .into_iter()
+        .enumerate()
+        .filter(|(i, entry)| match entry {
+            Entry::Property { property, .. } => kept.get(&property.key) == Some(i),
+            _ => true,
+        })
+        .map(|(_, entry)| entry)
+        .collect()
+}
+
+/// Extracts directives such as `@since 1.2` from the comments of a properties
+/// file, returning each directive's name and value in order.
+///
+/// A directive is a comment whose text, after the marker and any whitespace,
+/// starts with `prefix` immediately followed by a name. The value is the rest
+/// of the comment, with surrounding whitespace removed.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let input = b"# @since 1.2\n# plain comment\nkey=value\n";
+/// let directives = extract_comment_directives(input, "@").unwrap();
+/// assert_eq!(vec![(String::from("since"), String::from("1.2"))], directives);
+/// ```
+pub fn extract_comment_directives(
+    input: &[u8],
+    prefix: &str,
+) -> Result<Vec<(String, String)>, ParseError> {
+    let lines = parser::lines(input, &mut ParseOptions::default())?;
+    let directives = lines
+        .into_iter()
+        .filter_map(|line| match line {
+            Line::Comment(raw) => {
+                let text = comment(&decode(raw)).text;
+                let directive = text.trim().strip_prefix(prefix)?;
+                let (name, value) = match directive.find(char::is_whitespace) {
+                    Some(i) => directive.split_at(i),
+                    None => (directive, ""),
+                };
+                if name.is_empty() {
+                    None
+                } else {
+                    Some((name.to_string(), value.trim().to_string()))
+                }
+            }
+            _ => None,
+        })
+        .collect();
+    Ok(directives)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indented_comment_round_trip() {
+        let input = b"    # note\n\t! other\nkey=value\n";
+        let doc = parse_with_comments(input).unwrap();
+        assert_eq!(
+            Entry::Comment(Comment {
+                indent: String::from("    "),
+                marker: '#',
+                text: String::from(" note"),
+                eol: String::from("\n"),
+            }),
+            doc.entries[0]
+        );
+        assert_eq!(input.to_vec(), doc.to_bytes());
+
+        let mut opts = ParseOptions {
+            whitespace_chars: Some(&['\u{a0}']),
+            ..Default::default()
+        };
+        let input = b"\xa0# note\nkey=value\n";
+        let doc = parse_with_comments_and_options(input, &mut opts).unwrap();
+        match &doc.entries[0] {
+            Entry::Comment(c) => assert_eq!(("\u{a0}", '#'), (c.indent.as_str(), c.marker)),
+            e => panic!("expected a comment, got {:?}", e),
+        }
+        assert_eq!(input.to_vec(), doc.to_bytes());
+    }
+
+    #[test]
+    fn test_mixed_round_trip() {
+        let input =
+            b"# header\r\n\r\n  key   :  value \\\r\n   continued\r\n  \n\xe9=caf\xe9\n#last";
+        let doc = parse_with_comments(input).unwrap();
+        let props: Vec<&Property> = doc.properties().collect();
+        assert_eq!(2, props.len());
+        assert_eq!("value continued", props[0].value);
+        assert_eq!(input.to_vec(), doc.to_bytes());
+    }
+
+    #[test]
+    fn test_edited_property() {
+        let mut doc = parse_with_comments(b"# c\nkey = value\n").unwrap();
+        doc.entries[1] = Entry::Property {
+            property: Property {
+                key: String::from("new key"),
+                value: String::from(" padded"),
+            },
+            raw: None,
+            trailing_comment: None,
+        };
+        assert_eq!(b"# c\nnew\\ key=\\ padded\n".to_vec(), doc.to_bytes());
+    }
+
+    #[test]
+    fn test_trailing_comment_round_trip() {
+        let mut opts = ParseOptions {
+            inline_comments: true,
+            ..Default::default()
+        };
+        let input = b"key = first \\\n    second   # explained\nother=#fff\n";
+        let mut doc = parse_with_comments_and_options(input, &mut opts).unwrap();
+        assert_eq!(input.to_vec(), doc.to_bytes());
+        match &mut doc.entries[0] {
+            Entry::Property {
+                property,
+                raw,
+                trailing_comment,
+            } => {
+                assert_eq!("first second", property.value);
+                assert_eq!(Some(String::from("# explained")), *trailing_comment);
+                property.value = String::from("changed");
+                *raw = None;
+            }
+            e => panic!("expected a property, got {:?}", e),
+        }
+        assert_eq!(
+            b"key=changed # explained\nother=#fff\n".to_vec(),
+            doc.to_bytes()
+        );
+
+        // an edited value holding a marker is escaped so it reads back whole
+        match &mut doc.entries[0] {
+            Entry::Property { property, .. } => property.value = String::from("x #y !z"),
+            e => panic!("expected a property, got {:?}", e),
+        }
+        let bytes = doc.to_bytes();
+        assert_eq!(b"key=x \\#y \\!z # explained\nother=#fff\n".to_vec(), bytes);
+        let reparsed = parse_with_comments_and_options(&bytes, &mut opts).unwrap();
+        match &reparsed.entries[0] {
+            Entry::Property {
+                property,
+                trailing_comment,
+                ..
+            } => {
+                assert_eq!("x #y !z", property.value);
+                assert_eq!(Some(String::from("# explained")), *trailing_comment);
+            }
+            e => panic!("expected a property, got {:?}", e),
+        }
+
+        // trailing whitespace is not taken as the space before the comment
+        for value in ["x ", "x  ", " ", "x\\ "] {
+            let doc = Document {
+                entries: vec![Entry::Property {
+                    property: Property {
+                        key: String::from("k"),
+                        value: String::from(value),
+                    },
+                    raw: None,
+                    trailing_comment: Some(String::from("# c")),
+                }],
+            };
+            let bytes = doc.to_bytes();
+            let reparsed = parse_with_comments_and_options(&bytes, &mut opts).unwrap();
+            assert_eq!(doc.properties().next(), reparsed.properties().next());
+        }
+
+        // without inline comments the marker is part of the value
+        let doc = parse_with_comments(input).unwrap();
+        assert_eq!(
+            "first second   # explained",
+            doc.properties().next().unwrap().value
+        );
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let input = b"a=1\n# override\na = 2\nb=3\na=4\n";
+        let doc = parse_with_comments(input).unwrap();
+        let values: Vec<(&str, &str)> = doc
+            .properties()
+            .map(|p| (p.key.as_str(), p.value.as_str()))
+            .collect();
+        assert_eq!(vec![("a", "1"), ("a", "2"), ("b", "3"), ("a", "4")], values);
+        assert_eq!(input.to_vec(), doc.to_bytes());
+
+        let last = Document {
+            entries: resolve_duplicates(doc.entries.clone(), DuplicatePolicy::KeepLast),
+        };
+        assert_eq!(b"# override\nb=3\na=4\n".to_vec(), last.to_bytes());
+        let first = Document {
+            entries: resolve_duplicates(doc.entries, DuplicatePolicy::KeepFirst),
+        };
+        assert_eq!(b"a=1\n# override\nb=3\n".to_vec(), first.to_bytes());
+    }
+
+    #[test]
+    fn test_extract_comment_directives() {
+        let input = b"# @since 1.2\n\
+                      #   @author  Jane Doe  \n\
+                      ! @deprecated\n\
+                      # not a @directive\n\
+                      # @ nameless\n\
+                      key=value # @inline is part of the value\n\
+                      \t#@since 1.3\n";
+        let directives: Vec<(String, String)> = extract_comment_directives(input, "@").unwrap();
+        let pairs: Vec<(&str, &str)> = directives
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("since", "1.2"),
+                ("author", "Jane Doe"),
+                ("deprecated", ""),
+                ("since", "1.3"),
+            ],
+            pairs
+        );
+        assert!(extract_comment_directives(b"# @a b\n=x", "@").is_err());
+    }
+}
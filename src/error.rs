@@ -0,0 +1,399 @@
+//! Error types returned by the convenience functions of this crate
+use crate::line_index::line_ends;
+use crate::ValidationError;
+use std::fmt;
+use std::io;
+
+/// An error returned when a required key is not present.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissingKeyError {
+    /// The key which was not found
+    pub key: String,
+}
+
+impl fmt::Display for MissingKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "missing required key `{}`", self.key)
+    }
+}
+
+impl std::error::Error for MissingKeyError {}
+
+/// An error returned when a value is not one of the allowed values of an
+/// enumeration, by [`Properties::get_enum`](crate::Properties::get_enum).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidEnumError {
+    /// The key of the value
+    pub key: String,
+    /// The value which is not allowed
+    pub value: String,
+    /// The allowed values, in the order they were given
+    pub allowed: Vec<String>,
+}
+
+impl fmt::Display for InvalidEnumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "key `{}`: `{}` is not one of ", self.key, self.value)?;
+        for (i, allowed) in self.allowed.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "`{}`", allowed)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidEnumError {}
+
+/// An error returned when properties can not be converted into a map.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MapError {
+    /// A property has an empty key
+    EmptyKey {
+        /// The index of the property within the converted properties
+        index: usize,
+    },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::EmptyKey { index } => write!(f, "property {} has an empty key", index),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+/// An error returned when the input is not a valid properties file.
+///
+/// Lines and columns are counted from 1, with columns counted in bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// A line could not be parsed as a comment, blank line or property
+    Syntax {
+        /// The line the invalid property starts on
+        line: usize,
+        /// The column the invalid property starts at
+        column: usize,
+    },
+    /// A separator differs from the first one in the input, when parsing with
+    /// [`ParseOptions::consistent_separator`](crate::ParseOptions::consistent_separator)
+    MixedSeparator {
+        /// The line of the differing separator
+        line: usize,
+        /// The column of the differing separator
+        column: usize,
+        /// The first separator in the input
+        expected: char,
+        /// The differing separator
+        found: char,
+    },
+    /// A separator is directly followed by another, as in `key==value`, when
+    /// parsing with
+    /// [`ParseOptions::reject_double_separator`](crate::ParseOptions::reject_double_separator)
+    DoubleSeparator {
+        /// The line of the second separator
+        line: usize,
+        /// The column of the second separator
+        column: usize,
+    },
+    /// The marker line was not found, when parsing with
+    /// [`parse_after_marker`](crate::parse_after_marker) and requiring it.
+    /// The position is the end of the input
+    MissingMarker {
+        /// The line the input ended on
+        line: usize,
+        /// The column the input ended at
+        column: usize,
+    },
+    /// The input ended within a property which a line continuation carries on
+    /// to the next line, when reading from a stream. More input may complete it
+    UnexpectedEof {
+        /// The line the input ended on
+        line: usize,
+        /// The column the input ended at
+        column: usize,
+    },
+}
+
+impl ParseError {
+    /// Creates an error for the position `remaining` starts at within `input`
+    pub(crate) fn syntax(input: &[u8], remaining: &[u8]) -> Self {
+        let (line, column) = position(input, input.len() - remaining.len());
+        ParseError::Syntax { line, column }
+    }
+
+    /// Creates an error for a separator at `offset` within `input` which
+    /// differs from the first separator
+    pub(crate) fn mixed_separator(
+        input: &[u8],
+        offset: usize,
+        expected: char,
+        found: char,
+    ) -> Self {
+        let (line, column) = position(input, offset);
+        ParseError::MixedSeparator {
+            line,
+            column,
+            expected,
+            found,
+        }
+    }
+
+    /// Creates an error for a separator at `offset` within `input` which
+    /// directly follows another
+    pub(crate) fn double_separator(input: &[u8], offset: usize) -> Self {
+        let (line, column) = position(input, offset);
+        ParseError::DoubleSeparator { line, column }
+    }
+
+    /// Creates an error for a marker line which is not within `input`
+    pub(crate) fn missing_marker(input: &[u8]) -> Self {
+        let (line, column) = position(input, input.len());
+        ParseError::MissingMarker { line, column }
+    }
+
+    /// Creates an error for `input` ending within a continued property
+    pub(crate) fn unexpected_eof(input: &[u8]) -> Self {
+        let (line, column) = position(input, input.len());
+        ParseError::UnexpectedEof { line, column }
+    }
+
+    /// Moves the error down by `lines` lines, for an error within a part of a
+    /// larger input
+    pub(crate) fn offset_lines(mut self, lines: usize) -> Self {
+        match &mut self {
+            ParseError::Syntax { line, .. }
+            | ParseError::MixedSeparator { line, .. }
+            | ParseError::DoubleSeparator { line, .. }
+            | ParseError::MissingMarker { line, .. }
+            | ParseError::UnexpectedEof { line, .. } => *line += lines,
+        }
+        self
+    }
+
+    /// Converts an error from the parser of `input`
+    pub(crate) fn from_nom(input: &[u8], err: nom::Err<nom::error::Error<&[u8]>>) -> Self {
+        match err {
+            nom::Err::Error(e) | nom::Err::Failure(e) => Self::syntax(input, e.input),
+            nom::Err::Incomplete(_) => Self::syntax(input, &[]),
+        }
+    }
+
+    /// Returns the line the error occurred on
+    pub fn line(&self) -> usize {
+        match *self {
+            ParseError::Syntax { line, .. }
+            | ParseError::MixedSeparator { line, .. }
+            | ParseError::DoubleSeparator { line, .. }
+            | ParseError::MissingMarker { line, .. }
+            | ParseError::UnexpectedEof { line, .. } => line,
+        }
+    }
+
+    /// Returns the column the error occurred at
+    pub fn column(&self) -> usize {
+        match *self {
+            ParseError::Syntax { column, .. }
+            | ParseError::MixedSeparator { column, .. }
+            | ParseError::DoubleSeparator { column, .. }
+            | ParseError::MissingMarker { column, .. }
+            | ParseError::UnexpectedEof { column, .. } => column,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Syntax { line, column } => {
+                write!(f, "invalid property at line {}, column {}", line, column)
+            }
+            ParseError::MixedSeparator {
+                line,
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "separator `{}` at line {}, column {} differs from the `{}` used before",
+                found, line, column, expected
+            ),
+            ParseError::DoubleSeparator { line, column } => write!(
+                f,
+                "separator at line {}, column {} follows another separator",
+                line, column
+            ),
+            ParseError::MissingMarker { line, column } => write!(
+                f,
+                "marker line not found before the end of input at line {}, column {}",
+                line, column
+            ),
+            ParseError::UnexpectedEof { line, column } => write!(
+                f,
+                "unexpected end of input within a property at line {}, column {}",
+                line, column
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// An error returned when loading a configuration, covering every way
+/// [`parse_and_validate`](crate::parse_and_validate) and the validating
+/// lookups of [`Properties`](crate::Properties) can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The input is not a valid properties file
+    Parse(ParseError),
+    /// The properties do not satisfy the schema. Holds every failure
+    Validation(Vec<ValidationError>),
+    /// A required key is not present
+    Missing(MissingKeyError),
+    /// A required list has no items
+    EmptyList {
+        /// The key of the list
+        key: String,
+    },
+}
+
+impl From<ParseError> for ConfigError {
+    fn from(e: ParseError) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+impl From<MissingKeyError> for ConfigError {
+    fn from(e: MissingKeyError) -> Self {
+        ConfigError::Missing(e)
+    }
+}
+
+impl From<Vec<ValidationError>> for ConfigError {
+    fn from(errors: Vec<ValidationError>) -> Self {
+        ConfigError::Validation(errors)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Parse(e) => e.fmt(f),
+            ConfigError::Validation(errors) => {
+                for (i, e) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("; ")?;
+                    }
+                    e.fmt(f)?;
+                }
+                Ok(())
+            }
+            ConfigError::Missing(e) => e.fmt(f),
+            ConfigError::EmptyList { key } => write!(f, "required list `{}` is empty", key),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Parse(e) => Some(e),
+            ConfigError::Missing(e) => Some(e),
+            ConfigError::Validation(_) | ConfigError::EmptyList { .. } => None,
+        }
+    }
+}
+
+/// An error returned when reading properties from a stream.
+#[derive(Debug)]
+pub enum PropsError {
+    /// Reading from the stream failed
+    Io(io::Error),
+    /// The input is not a valid properties file
+    Parse(ParseError),
+}
+
+impl From<io::Error> for PropsError {
+    fn from(e: io::Error) -> Self {
+        PropsError::Io(e)
+    }
+}
+
+impl From<ParseError> for PropsError {
+    fn from(e: ParseError) -> Self {
+        PropsError::Parse(e)
+    }
+}
+
+impl fmt::Display for PropsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropsError::Io(e) => write!(f, "failed to read properties: {}", e),
+            PropsError::Parse(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PropsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PropsError::Io(e) => Some(e),
+            PropsError::Parse(e) => Some(e),
+        }
+    }
+}
+
+/// Returns the line and column of a byte offset within `input`. Any of
+/// `"\r\n"`, `"\r"` or `"\n"` end a line.
+fn position(input: &[u8], offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut line_start = 0;
+    for end in line_ends(input).take_while(|&end| end <= offset) {
+        line += 1;
+        line_start = end;
+    }
+    (line, offset - line_start + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position() {
+        let input = b"a=1\r\nb=2\rc=3\n\nd";
+        assert_eq!((1, 1), position(input, 0));
+        assert_eq!((1, 4), position(input, 3));
+        assert_eq!((1, 5), position(input, 4));
+        assert_eq!((2, 1), position(input, 5));
+        assert_eq!((3, 2), position(input, 10));
+        assert_eq!((5, 1), position(input, 14));
+        assert_eq!((5, 2), position(input, 15));
+    }
+
+    #[test]
+    fn test_display() {
+        let err = ParseError::Syntax { line: 3, column: 1 };
+        assert_eq!("invalid property at line 3, column 1", err.to_string());
+        let err = ParseError::mixed_separator(b"a=1\nb : 2", 6, '=', ':');
+        assert_eq!((2, 3), (err.line(), err.column()));
+        assert_eq!(
+            "separator `:` at line 2, column 3 differs from the `=` used before",
+            err.to_string()
+        );
+        let err = ParseError::double_separator(b"a==1", 2);
+        assert_eq!(
+            "separator at line 1, column 3 follows another separator",
+            err.to_string()
+        );
+        let err = ParseError::unexpected_eof(b"a=1\\\n  ").offset_lines(2);
+        assert_eq!((4, 3), (err.line(), err.column()));
+        assert_eq!(
+            "unexpected end of input within a property at line 4, column 3",
+            err.to_string()
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Deduplication of keys shared between many parsed files
+use crate::{parse, ParseError};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// A property whose key is shared with every other property parsed through
+/// the same [`Interner`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd)]
+pub struct InternedProperty {
+    /// The shared identifier for the property
+    pub key: Arc<str>,
+    /// The value of the property
+    pub value: String,
+}
+
+/// A cache of keys which hands out a single shared allocation per distinct
+/// key.
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    keys: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    /// Creates an empty interner
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the shared allocation for `key`, creating it if it has not been
+    /// seen before.
+    pub fn intern(&mut self, key: &str) -> Arc<str> {
+        match self.keys.get(key) {
+            Some(k) => Arc::clone(k),
+            None => {
+                let k: Arc<str> = Arc::from(key);
+                self.keys.insert(Arc::clone(&k));
+                k
+            }
+        }
+    }
+
+    /// Returns the number of distinct keys interned
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns whether no keys have been interned
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Parses a properties file like [`parse`], deduplicating keys through
+/// `interner` so that files with overlapping keys share key allocations.
+///
+/// ```
+/// use props_rs::*;
+/// use std::sync::Arc;
+///
+/// let mut interner = Interner::new();
+/// let a = parse_interned(b"db.host=a", &mut interner).unwrap();
+/// let b = parse_interned(b"db.host=b", &mut interner).unwrap();
+/// assert!(Arc::ptr_eq(&a[0].key, &b[0].key));
+/// ```
+pub fn parse_interned(
+    input: &[u8],
+    interner: &mut Interner,
+) -> Result<Vec<InternedProperty>, ParseError> {
+    Ok(parse(input)?
+        .into_iter()
+        .map(|p| InternedProperty {
+            key: interner.intern(&p.key),
+            value: p.value,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_keys() {
+        let mut interner = Interner::new();
+        let first = parse_interned(b"db.host=a\ndb.port=1\n", &mut interner).unwrap();
+        let second = parse_interned(b"db.port=2\ndb.user=u\n", &mut interner).unwrap();
+        assert_eq!(3, interner.len());
+        assert!(Arc::ptr_eq(&first[1].key, &second[0].key));
+        assert_eq!("1", first[1].value);
+        assert_eq!("2", second[0].value);
+        assert!(!Arc::ptr_eq(&first[0].key, &second[1].key));
+    }
+}
@@ -20,22 +20,349 @@
 #![deny(missing_docs)]
 #![deny(rustdoc::missing_crate_level_docs)]
 
+#[cfg(feature = "cache")]
+mod cache;
+mod document;
+mod error;
+mod interner;
+mod line_index;
 mod parser;
-pub use parser::Property;
-use std::collections::HashMap;
+mod properties;
+mod resolver;
+mod schema;
+mod stream;
+mod summary;
+mod writer;
+#[cfg(feature = "cache")]
+pub use cache::parse_cached;
+pub use document::{
+    extract_comment_directives, parse_with_comments, parse_with_comments_and_options,
+    resolve_duplicates, Comment, Document, DuplicatePolicy, Entry,
+};
+pub use error::{ConfigError, InvalidEnumError, MapError, MissingKeyError, ParseError, PropsError};
+pub use interner::{parse_interned, InternedProperty, Interner};
+pub use line_index::{line_index, LineIndex};
+use parser::Line;
+pub use parser::{
+    EscapeKind, EscapedProperty, KeyBoundary, ParseOptions, Property, TraceEvent,
+    RECOGNIZED_ESCAPES,
+};
+pub use properties::{OccupiedEntry, Properties, PropertyEntry, VacantEntry};
+pub use resolver::{Precedence, ResolveError, Resolver};
+pub use schema::{
+    parse_and_validate, validate, Constraint, Schema, ValidationError, ValidationErrorKind,
+};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+pub use stream::{parse_buf_read, parse_reader, transform};
+pub use summary::{summarize, Summary};
+pub use writer::{to_bytes, to_bytes_java, would_change, write, write_filtered, WriteOptions};
 
 /// Parses a properties file and returns a [`Vec`] of properties. There may
 /// potentially be properties with duplicate keys in the returned [`Vec`].
 ///
 /// Use the [`to_map`] convenience function to convert the vec into a set of
 /// properties with unique keys.
-pub fn parse(input: &[u8]) -> Result<Vec<Property>, nom::Err<nom::error::Error<&[u8]>>> {
+///
+/// If the input is invalid, the returned [`ParseError`] holds the position of
+/// the first line which could not be parsed.
+pub fn parse(input: &[u8]) -> Result<Vec<Property>, ParseError> {
     match parser::parser(input) {
         Ok((_, v)) => Ok(v),
-        Err(e) => Err(e),
+        Err(e) => Err(ParseError::from_nom(input, e)),
+    }
+}
+
+/// Parses a properties file like [`parse`], panicking with the position of the
+/// error if it is invalid.
+///
+/// This is intended for tests and quick scripts where the input is known to be
+/// valid. Prefer [`parse`] elsewhere.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let parsed = parse_or_panic(b"key=value");
+/// assert_eq!("value", parsed[0].value);
+/// ```
+pub fn parse_or_panic(input: &[u8]) -> Vec<Property> {
+    match parse(input) {
+        Ok(v) => v,
+        Err(e) => panic!("failed to parse properties: {}", e),
+    }
+}
+
+/// Parses the properties which follow a marker line within a larger text,
+/// such as a script with its configuration embedded after a `__PROPS__` line.
+///
+/// Everything up to and including the first line consisting of `marker`
+/// (ignoring trailing whitespace) is skipped. If there is no such line, no
+/// properties are returned, or with `require_marker` an error of
+/// [`ParseError::MissingMarker`]. Error positions are relative to the whole
+/// input.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let input = b"#!/bin/sh\necho hello\n__PROPS__\nkey=value\n";
+/// let parsed = parse_after_marker(input, b"__PROPS__", true).unwrap();
+/// assert_eq!("value", parsed[0].value);
+/// assert_eq!(Ok(vec![]), parse_after_marker(b"echo hello\n", b"__PROPS__", false));
+/// assert!(parse_after_marker(b"echo hello\n", b"__PROPS__", true).is_err());
+/// ```
+pub fn parse_after_marker(
+    input: &[u8],
+    marker: &[u8],
+    require_marker: bool,
+) -> Result<Vec<Property>, ParseError> {
+    let mut rest = input;
+    while !rest.is_empty() {
+        let end = match rest.iter().position(|&b| b == b'\r' || b == b'\n') {
+            Some(i) if rest[i..].starts_with(b"\r\n") => i + 2,
+            Some(i) => i + 1,
+            None => rest.len(),
+        };
+        let (line, next) = rest.split_at(end);
+        let trimmed = match line.iter().rposition(|b| !b" \t\x0c\r\n".contains(b)) {
+            Some(i) => &line[..=i],
+            None => &[],
+        };
+        if trimmed == marker {
+            return match parser::parser(next) {
+                Ok((_, v)) => Ok(v),
+                Err(e) => Err(ParseError::from_nom(input, e)),
+            };
+        }
+        rest = next;
+    }
+    if require_marker {
+        Err(ParseError::missing_marker(input))
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Parses a properties file like [`parse`] from an iterator of bytes, such as
+/// data spread across several buffers.
+///
+/// The parser needs the whole input at once, so the bytes are first collected
+/// into a single buffer.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let parsed = parse_from_iter(b"a=1\n".iter().chain(b"b=2").copied()).unwrap();
+/// assert_eq!(2, parsed.len());
+/// ```
+pub fn parse_from_iter<I: IntoIterator<Item = u8>>(iter: I) -> Result<Vec<Property>, ParseError> {
+    let input: Vec<u8> = iter.into_iter().collect();
+    parse(&input)
+}
+
+/// A property along with the line it starts on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NumberedProperty {
+    /// The parsed property
+    pub property: Property,
+    /// The line the property starts on, counted from 1
+    pub line: usize,
+}
+
+/// Parses a properties file like [`parse`], additionally returning the line
+/// each property starts on.
+///
+/// Lines are counted as the parser advances, in a single pass over the input.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let parsed = parse_numbered(b"# comment\na=1\n\nb=2 \\\n  3\nc=4").unwrap();
+/// let lines: Vec<usize> = parsed.iter().map(|p| p.line).collect();
+/// assert_eq!(vec![2, 4, 6], lines);
+/// ```
+pub fn parse_numbered(input: &[u8]) -> Result<Vec<NumberedProperty>, ParseError> {
+    let lines = parser::lines(input, &mut ParseOptions::default())?;
+    let mut line = 1;
+    let mut props = Vec::new();
+    for l in lines.into_iter() {
+        let raw = match l {
+            Line::Blank(raw) | Line::Comment(raw) => raw,
+            Line::Property(property, _, raw) => {
+                props.push(NumberedProperty { property, line });
+                raw
+            }
+        };
+        line += line_index::line_ends(raw).count();
+    }
+    Ok(props)
+}
+
+/// Parses a single value from the start of `input`, stopping before the first
+/// unescaped character in `terminators`, for values embedded in a delimited
+/// context such as `{key=value}`.
+///
+/// The value also ends at the end of its line, and escapes and line
+/// continuations are decoded as in a property value. Returns the decoded value
+/// and the number of bytes consumed, which excludes the terminator.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let (value, len) = parse_value_until(br"a\;b;rest", &[';']).unwrap();
+/// assert_eq!(("a;b", 4), (value.as_str(), len));
+/// ```
+pub fn parse_value_until(
+    input: &[u8],
+    terminators: &[char],
+) -> Result<(String, usize), ParseError> {
+    match parser::value_until(input, terminators) {
+        Ok((rest, v)) => Ok((v, input.len() - rest.len())),
+        Err(e) => Err(ParseError::from_nom(input, e)),
+    }
+}
+
+/// Parses a properties file like [`parse`], according to the given options.
+pub fn parse_with_options(
+    input: &[u8],
+    opts: &mut ParseOptions,
+) -> Result<Vec<Property>, ParseError> {
+    parser::parser_with_options(input, opts)
+}
+
+/// Parses a properties file according to the given options, skipping each
+/// line which fails to parse rather than failing. Returns the properties which
+/// did parse, along with an error describing every skipped line.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let mut opts = ParseOptions {
+///     reject_double_separator: true,
+///     ..Default::default()
+/// };
+/// let (props, errors) = parse_lenient(b"a=\nb==2\n=3\nc=4", &mut opts);
+/// let keys: Vec<&str> = props.iter().map(|p| p.key.as_str()).collect();
+/// assert_eq!(vec!["a", "c"], keys);
+/// assert_eq!(
+///     vec![
+///         ParseError::DoubleSeparator { line: 2, column: 3 },
+///         ParseError::Syntax { line: 3, column: 1 },
+///     ],
+///     errors
+/// );
+/// ```
+pub fn parse_lenient(input: &[u8], opts: &mut ParseOptions) -> (Vec<Property>, Vec<ParseError>) {
+    parser::parser_lenient(input, opts)
+}
+
+/// Parses a properties file like [`parse`], additionally returning the
+/// position and kind of every character escape within each property.
+///
+/// Offsets are in bytes from the start of `input` and point at the backslash
+/// beginning the escape. Line continuations are not included.
+pub fn parse_with_escapes(input: &[u8]) -> Result<Vec<EscapedProperty>, ParseError> {
+    match parser::parser_with_escapes(input) {
+        Ok((_, v)) => Ok(v),
+        Err(e) => Err(ParseError::from_nom(input, e)),
     }
 }
 
+/// Splits a value which itself holds key-value pairs, such as
+/// `user=sa;password=x`, into properties.
+///
+/// Pairs are separated by `pair_sep` and each key is separated from its value
+/// by the first `kv_sep`. A pair without `kv_sep` has an empty value, and
+/// empty pairs are skipped. Unescaped whitespace around keys and values is
+/// removed.
+///
+/// A backslash escapes the following character as in a property, so `\;` is
+/// a literal `;` and `\u00e9` is `é`. A malformed `\u` escape is kept as
+/// written. Note that in a file the backslash must itself be escaped,
+/// as in `jdbc.props=a=1\\;2` for a nested value of `a=1\;2`.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let nested = parse_nested_value("user=sa; password=x", ';', '=');
+/// assert_eq!("user", nested[0].key);
+/// assert_eq!("x", nested[1].value);
+/// ```
+pub fn parse_nested_value(value: &str, pair_sep: char, kv_sep: char) -> Vec<Property> {
+    // each character along with whether it was escaped
+    let mut chars = Vec::with_capacity(value.len());
+    let mut iter = value.chars();
+    while let Some(c) = iter.next() {
+        match (c, iter.clone().next()) {
+            ('\\', Some('u')) => {
+                // the digits of an escape are ASCII, so `rest` ends on a char boundary
+                let input = iter.as_str();
+                match parser::unicode_escape(input.as_bytes()) {
+                    Ok((rest, decoded)) => {
+                        iter = input[input.len() - rest.len()..].chars();
+                        chars.push((decoded, true));
+                    }
+                    Err(_) => chars.push((c, false)),
+                }
+            }
+            ('\\', Some(e)) => {
+                iter.next();
+                chars.push((parser::escaped_char_to_char(e), true));
+            }
+            _ => chars.push((c, false)),
+        }
+    }
+    fn trimmed(chars: &[(char, bool)]) -> String {
+        let blank = |&(c, escaped): &(char, bool)| !escaped && c.is_whitespace();
+        let start = chars.iter().position(|c| !blank(c)).unwrap_or(chars.len());
+        let end = chars
+            .iter()
+            .rposition(|c| !blank(c))
+            .map_or(start, |i| i + 1);
+        chars[start..end].iter().map(|&(c, _)| c).collect()
+    }
+    chars
+        .split(|&(c, escaped)| !escaped && c == pair_sep)
+        .filter(|pair| !trimmed(pair).is_empty())
+        .map(|pair| {
+            let (key, value) = match pair.iter().position(|&(c, e)| !e && c == kv_sep) {
+                Some(i) => (&pair[..i], &pair[i + 1..]),
+                None => (pair, &[][..]),
+            };
+            Property {
+                key: trimmed(key),
+                value: trimmed(value),
+            }
+        })
+        .collect()
+}
+
+/// Converts every `\r\n` and lone `\r` line ending in `input` to `\n`,
+/// for diffing or fingerprinting files regardless of their line endings.
+///
+/// Only physical carriage return bytes are converted. Escape sequences such
+/// as the two characters `\r` within a value are left untouched.
+///
+/// ```
+/// use props_rs::*;
+///
+/// assert_eq!(b"a=1\nb=2\n".to_vec(), normalize_line_endings(b"a=1\r\nb=2\r"));
+/// ```
+pub fn normalize_line_endings(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut bytes = input.iter().peekable();
+    while let Some(&b) = bytes.next() {
+        if b == b'\r' {
+            if bytes.peek() == Some(&&b'\n') {
+                bytes.next();
+            }
+            out.push(b'\n');
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
 /// A convenience function which converts a [`Vec`] of [`Property`] into a set of
 /// [`Property`] stored in a [`HashMap`]
 pub fn to_map(props: Vec<Property>) -> HashMap<String, String> {
@@ -46,10 +373,148 @@ pub fn to_map(props: Vec<Property>) -> HashMap<String, String> {
     map
 }
 
+/// Converts a [`Vec`] of [`Property`] into a map like [`to_map`], failing if
+/// any key is empty so that nothing is stored under the key `""`.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let map = try_to_map(parse(b"a=1").unwrap()).unwrap();
+/// assert_eq!("1", map["a"]);
+/// ```
+pub fn try_to_map(props: Vec<Property>) -> Result<HashMap<String, String>, MapError> {
+    let mut map = HashMap::with_capacity(props.len());
+    for (index, prop) in props.into_iter().enumerate() {
+        if prop.key.is_empty() {
+            return Err(MapError::EmptyKey { index });
+        }
+        map.insert(prop.key, prop.value);
+    }
+    Ok(map)
+}
+
+/// Converts a slice of [`Property`] into a map which borrows its keys and
+/// values from the slice. Later duplicates override earlier ones, as in
+/// [`to_map`].
+///
+/// Entries start out as [`Cow::Borrowed`], so callers may take ownership of
+/// specific entries later without cloning the rest.
+pub fn to_cow_map(props: &[Property]) -> HashMap<Cow<'_, str>, Cow<'_, str>> {
+    let mut map = HashMap::with_capacity(props.len());
+    for prop in props.iter() {
+        map.insert(
+            Cow::Borrowed(prop.key.as_str()),
+            Cow::Borrowed(prop.value.as_str()),
+        );
+    }
+    map
+}
+
+/// Converts a slice of [`Property`] into a map of environment variables
+/// suitable for [`std::process::Command::envs`], using `mapper` to turn each
+/// key into a variable name.
+///
+/// When several keys map to the same name the last one wins, just as
+/// duplicate keys do in [`to_map`].
+///
+/// ```
+/// use props_rs::*;
+///
+/// let parsed = parse(b"db.host=localhost").unwrap();
+/// let env = to_env_map(&parsed, |k| k.replace('.', "_").to_uppercase());
+/// assert_eq!("localhost", env.get("DB_HOST").unwrap());
+/// ```
+pub fn to_env_map(props: &[Property], mapper: impl Fn(&str) -> String) -> HashMap<String, String> {
+    let mut map = HashMap::with_capacity(props.len());
+    for prop in props.iter() {
+        map.insert(mapper(&prop.key), prop.value.clone());
+    }
+    map
+}
+
+/// Returns the value of a key which must be present, or an error naming the
+/// key. When the key is duplicated the last value is returned, as in
+/// [`to_map`].
+pub fn expect_key<'a>(props: &'a [Property], key: &str) -> Result<&'a str, MissingKeyError> {
+    props
+        .iter()
+        .rev()
+        .find(|p| p.key == key)
+        .map(|p| p.value.as_str())
+        .ok_or_else(|| MissingKeyError {
+            key: key.to_string(),
+        })
+}
+
+/// Returns whether two properties files define the same keys with the same
+/// values, ignoring order, comments, blank lines, the choice of separator,
+/// escapes and line continuations, and the whitespace the parser discards
+/// around keys and separators. Duplicated keys are resolved with the last
+/// value winning, as in [`to_map`].
+///
+/// Trailing whitespace is part of a value as in Java, so `a=1 ` and `a=1` are
+/// not equal.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let a = b"# settings\nhost = localhost\nport=80\nport=8080";
+/// let b = b"port:8080\n\n  host   localhost";
+/// assert_eq!(Ok(true), semantically_equal(a, b));
+/// assert_eq!(Ok(false), semantically_equal(a, b"host=localhost\nport=80"));
+/// ```
+pub fn semantically_equal(a: &[u8], b: &[u8]) -> Result<bool, ParseError> {
+    Ok(to_map(parse(a)?) == to_map(parse(b)?))
+}
+
+/// Compares the keys of two versions of a set of properties, returning the
+/// keys only in `new` and the keys only in `old`, in that order.
+///
+/// Values are ignored, so a key whose value changed appears in neither list.
+/// Each key is listed once, in the order it first appears.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let old = parse(b"a=1\nb=2").unwrap();
+/// let new = parse(b"b=3\nc=4").unwrap();
+/// assert_eq!((vec![String::from("c")], vec![String::from("a")]), diff_keys(&old, &new));
+/// ```
+pub fn diff_keys(old: &[Property], new: &[Property]) -> (Vec<String>, Vec<String>) {
+    fn only_in(props: &[Property], other: &[Property]) -> Vec<String> {
+        let other: HashSet<&str> = other.iter().map(|p| p.key.as_str()).collect();
+        let mut seen = HashSet::new();
+        props
+            .iter()
+            .map(|p| p.key.as_str())
+            .filter(|k| !other.contains(k) && seen.insert(*k))
+            .map(String::from)
+            .collect()
+    }
+    (only_in(new, old), only_in(old, new))
+}
+
 #[cfg(test)]
 mod tests {
+    use super::diff_keys;
+    use super::expect_key;
+    use super::normalize_line_endings;
     use super::parse;
+    use super::parse_after_marker;
+    use super::parse_from_iter;
+    use super::parse_nested_value;
+    use super::parse_numbered;
+    use super::parse_or_panic;
+    use super::parse_value_until;
+    use super::parse_with_escapes;
+    use super::parse_with_options;
+    use super::to_cow_map;
+    use super::to_env_map;
     use super::to_map;
+    use super::try_to_map;
+    use super::EscapeKind;
+    use super::ParseOptions;
+    use std::borrow::Cow;
 
     #[test]
     pub fn test_parse_simple() {
@@ -62,12 +527,34 @@ property2=test
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     pub fn test_broken_parse() {
         let v = br"=test
 ";
         assert_eq!(true, parse(v).is_err());
     }
 
+    #[test]
+    pub fn test_error_position() {
+        let v = b"a=1\r\n\n  =test\n";
+        let err = parse(v).unwrap_err();
+        assert_eq!(super::ParseError::Syntax { line: 3, column: 1 }, err);
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to parse properties: invalid property at line 3")]
+    pub fn test_parse_or_panic() {
+        parse_or_panic(b"key=value\n# comment\n=test\n");
+    }
+
+    #[test]
+    pub fn test_parse_from_iter() {
+        let parsed = parse_from_iter("key=value".bytes()).unwrap();
+        assert_eq!(parse(b"key=value").unwrap(), parsed);
+        let err = parse_from_iter("a=1\n=2".bytes()).unwrap_err();
+        assert_eq!(super::ParseError::Syntax { line: 2, column: 1 }, err);
+    }
+
     #[test]
     pub fn test_map_conversion() {
         let v = br"
@@ -80,4 +567,351 @@ property=t
         assert_eq!("t", res.get("property").unwrap());
         assert_eq!("test", res.get("property2").unwrap());
     }
+
+    #[test]
+    pub fn test_cow_map_conversion() {
+        let v = br"
+property=test
+property2=test
+property=t
+";
+        let props = parse(v).unwrap();
+        let res = to_cow_map(&props);
+        let owned = to_map(props.clone());
+        assert_eq!(owned.len(), res.len());
+        for (k, v) in owned.iter() {
+            assert_eq!(v, res.get(k.as_str()).unwrap());
+        }
+        match res.get("property").unwrap() {
+            Cow::Borrowed(v) => assert_eq!(props[2].value.as_ptr(), v.as_ptr()),
+            Cow::Owned(_) => panic!("expected a borrowed value"),
+        }
+    }
+
+    #[test]
+    pub fn test_expect_key() {
+        let props = parse(b"property=test\nproperty=t").unwrap();
+        assert_eq!(Ok("t"), expect_key(&props, "property"));
+        let err = expect_key(&props, "missing").unwrap_err();
+        assert_eq!("missing", err.key);
+    }
+
+    #[test]
+    pub fn test_parse_with_escapes() {
+        let v = br"
+key=one\ttwo\:three
+";
+        let res = parse_with_escapes(v).unwrap();
+        assert_eq!(1, res.len());
+        assert_eq!("one\ttwo:three", res[0].property.value);
+        assert_eq!(
+            vec![(8, EscapeKind::Tab), (13, EscapeKind::Other(':'))],
+            res[0].escapes
+        );
+    }
+
+    #[test]
+    pub fn test_env_map_conversion() {
+        let v = br"
+db.host=localhost
+db.port=5432
+db-port=5433
+";
+        let props = parse(v).unwrap();
+        let env = to_env_map(&props, |k| k.replace(['.', '-'], "_").to_uppercase());
+        assert_eq!(2, env.len());
+        assert_eq!("localhost", env.get("DB_HOST").unwrap());
+        // db-port collides with db.port and, being later, wins
+        assert_eq!("5433", env.get("DB_PORT").unwrap());
+    }
+
+    #[test]
+    pub fn test_parse_after_marker() {
+        let v = b"#!/usr/bin/env script
+key = not a property
+__PROPS__ \r
+property=test
+property2=test
+";
+        for require_marker in [false, true] {
+            let res = parse_after_marker(v, b"__PROPS__", require_marker).unwrap();
+            assert_eq!(2, res.len());
+            assert_eq!("property", res[0].key);
+            assert_eq!("test", res[1].value);
+        }
+
+        // no marker means no properties, or an error when it is required
+        assert_eq!(Ok(vec![]), parse_after_marker(v, b"__CONFIG__", false));
+        assert_eq!(
+            Err(super::ParseError::MissingMarker { line: 6, column: 1 }),
+            parse_after_marker(v, b"__CONFIG__", true)
+        );
+        // a marker with nothing after it is not missing
+        assert_eq!(
+            Ok(vec![]),
+            parse_after_marker(b"__PROPS__", b"__PROPS__", true)
+        );
+
+        // errors are reported relative to the whole input
+        let v = b"preamble\n__PROPS__\nkey=value\n=test\n";
+        let err = parse_after_marker(v, b"__PROPS__", false).unwrap_err();
+        assert_eq!(4, err.line());
+    }
+
+    #[test]
+    pub fn test_normalize_line_endings() {
+        let v = b"a=1\r\nb=2\rc=3\n\r\n\r\rd=\\r\\n";
+        assert_eq!(
+            b"a=1\nb=2\nc=3\n\n\n\nd=\\r\\n".to_vec(),
+            normalize_line_endings(v)
+        );
+        assert_eq!(
+            parse(v).unwrap(),
+            parse(&normalize_line_endings(v)).unwrap()
+        );
+        assert!(normalize_line_endings(b"").is_empty());
+    }
+
+    #[test]
+    pub fn test_diff_keys() {
+        let old = parse(b"kept=1\nremoved=2\nchanged=3\nkept=4").unwrap();
+        let new = parse(b"changed=30\nadded=5\nkept=1\nadded=6").unwrap();
+        let (added, removed) = diff_keys(&old, &new);
+        assert_eq!(vec![String::from("added")], added);
+        assert_eq!(vec![String::from("removed")], removed);
+        assert_eq!((vec![], vec![]), diff_keys(&old, &old));
+    }
+
+    #[test]
+    pub fn test_parse_numbered() {
+        let v = b"# c\r\na=1\r\rb = 2 \\\r\n  3\n\n  c \\\n\n";
+        let numbered = parse_numbered(v).unwrap();
+        let lines: Vec<(usize, &str)> = numbered
+            .iter()
+            .map(|p| (p.line, p.property.key.as_str()))
+            .collect();
+        assert_eq!(vec![(2, "a"), (4, "b"), (7, "c")], lines);
+        assert_eq!(
+            parse(v).unwrap(),
+            numbered.into_iter().map(|p| p.property).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    pub fn test_parse_numbered_scans_each_byte_once() {
+        let input: Vec<u8> = (0..50_000)
+            .flat_map(|i| format!("key{}=value{} \\\r\n  more\r# comment\n\n", i, i).into_bytes())
+            .collect();
+        let mut parsed = Vec::new();
+        let scanned = super::line_index::count_scanned(|| {
+            parsed = parse_numbered(&input).unwrap();
+        });
+        assert_eq!(input.len(), scanned);
+        assert_eq!(50_000, parsed.len());
+        let starts: Vec<usize> = parsed.iter().map(|p| p.line).take(3).collect();
+        assert_eq!(vec![1, 5, 9], starts);
+        assert_eq!(4 * 49_999 + 1, parsed[49_999].line);
+    }
+
+    #[test]
+    pub fn test_mixed_separators() {
+        let v = b"a=1\nb:2\nc 3\nd = 4\n  e  :  5";
+        let parsed = parse(v).unwrap();
+        let values: Vec<&str> = parsed.iter().map(|p| p.value.as_str()).collect();
+        assert_eq!(vec!["1", "2", "3", "4", "5"], values);
+
+        let mut opts = ParseOptions {
+            consistent_separator: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Err(super::ParseError::MixedSeparator {
+                line: 2,
+                column: 2,
+                expected: '=',
+                found: ':',
+            }),
+            parse_with_options(v, &mut opts)
+        );
+        let err = parse_with_options(b"a 1\nb:2\n\nc = 3", &mut opts).unwrap_err();
+        assert_eq!((4, 3), (err.line(), err.column()));
+        assert_eq!(
+            5,
+            parse_with_options(b"a=1\nb 2\nc\\:=3\nd=\ne=:", &mut opts)
+                .unwrap()
+                .len()
+        );
+    }
+
+    #[test]
+    pub fn test_semantically_equal() {
+        use super::{semantically_equal, ParseError};
+
+        let original = b"# database\r\ndb.host = db.local\r\ndb.port=5432\r\n\r\n! tuning\r\n\
+                         db.pool: 1, \\\r\n   2\r\ndb.port=6543";
+        let reformatted = b"db.pool=1, 2\n\
+                            db.port=6543\n\
+                            \tdb\\u002ehost   db.local\n";
+        assert_eq!(Ok(true), semantically_equal(original, reformatted));
+        assert_eq!(Ok(true), semantically_equal(b"", b"# only a comment\n\n"));
+
+        let changed = b"db.pool=1, 2\ndb.port=5432\ndb.host=db.local\n";
+        assert_eq!(Ok(false), semantically_equal(original, changed));
+        assert_eq!(Ok(false), semantically_equal(b"a=1", b"a=1 "));
+        assert_eq!(Ok(false), semantically_equal(b"a=1", b"a=1\nb="));
+        assert_eq!(
+            Err(ParseError::Syntax { line: 2, column: 1 }),
+            semantically_equal(b"a=1", b"a=1\n=2")
+        );
+    }
+
+    #[test]
+    pub fn test_parse_lenient() {
+        use super::{parse_lenient, ParseError};
+
+        let input = b"empty=\ndouble==x\nspaced = : y\nescaped=\\=z\n=orphan\nlast:ok";
+        let (props, errors) = parse_lenient(input, &mut ParseOptions::default());
+        let values: Vec<&str> = props.iter().map(|p| p.value.as_str()).collect();
+        assert_eq!(vec!["", "=x", ": y", "=z", "ok"], values);
+        assert_eq!(vec![ParseError::Syntax { line: 5, column: 1 }], errors);
+
+        let mut opts = ParseOptions {
+            reject_double_separator: true,
+            ..Default::default()
+        };
+        let (props, errors) = parse_lenient(input, &mut opts);
+        let keys: Vec<&str> = props.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(vec!["empty", "escaped", "last"], keys);
+        assert_eq!(
+            vec![
+                ParseError::DoubleSeparator { line: 2, column: 8 },
+                ParseError::DoubleSeparator {
+                    line: 3,
+                    column: 10
+                },
+                ParseError::Syntax { line: 5, column: 1 },
+            ],
+            errors
+        );
+        assert_eq!(
+            Err(ParseError::DoubleSeparator { line: 1, column: 3 }),
+            parse_with_options(b"a::b", &mut opts)
+        );
+
+        // a key with an empty value is never a diagnostic
+        let (props, errors) = parse_lenient(b"key=\nkey:\r\nkey =  ", &mut opts);
+        assert!(errors.is_empty());
+        assert!(props.iter().all(|p| p.is_empty_value()));
+    }
+
+    #[test]
+    pub fn test_parse_lenient_scans_each_byte_once() {
+        use super::{parse_lenient, ParseError};
+
+        let input: Vec<u8> = (0..40_000)
+            .flat_map(|i| format!("=bad{}\nk{}==v\nok=1\n", i, i).into_bytes())
+            .collect();
+        let mut opts = ParseOptions {
+            reject_double_separator: true,
+            ..Default::default()
+        };
+        let mut result = (vec![], vec![]);
+        let scanned = super::line_index::count_scanned(|| {
+            result = parse_lenient(&input, &mut opts);
+        });
+        assert_eq!(input.len(), scanned);
+        let (props, errors) = result;
+        assert_eq!(40_000, props.len());
+        assert_eq!(80_000, errors.len());
+        assert_eq!(
+            ParseError::Syntax {
+                line: 119_998,
+                column: 1
+            },
+            errors[79_998]
+        );
+        assert_eq!(
+            ParseError::DoubleSeparator {
+                line: 119_999,
+                column: 8
+            },
+            errors[79_999]
+        );
+    }
+
+    #[test]
+    pub fn test_try_to_map() {
+        let mut props = parse(b"a=1\nb=2\na=3").unwrap();
+        let map = try_to_map(props.clone()).unwrap();
+        assert_eq!(to_map(props.clone()), map);
+
+        props.insert(
+            1,
+            super::Property {
+                key: String::new(),
+                value: String::from("orphan"),
+            },
+        );
+        let err = try_to_map(props).unwrap_err();
+        assert_eq!(super::MapError::EmptyKey { index: 1 }, err);
+        assert_eq!("property 1 has an empty key", err.to_string());
+    }
+
+    #[test]
+    pub fn test_parse_value_until() {
+        assert_eq!(
+            (String::from("a b"), 3),
+            parse_value_until(b"a b;c=d", &[';']).unwrap()
+        );
+        assert_eq!(
+            (String::from("a;b"), 4),
+            parse_value_until(br"a\;b;c=d", &[';']).unwrap()
+        );
+        assert_eq!(
+            (String::from("no terminator"), 13),
+            parse_value_until(b"no terminator", &[';']).unwrap()
+        );
+        let err = parse_value_until(br"bad \u escape;", &[';']).unwrap_err();
+        assert_eq!((1, 5), (err.line(), err.column()));
+    }
+
+    #[test]
+    pub fn test_parse_nested_value() {
+        let pairs = |props: Vec<super::Property>| -> Vec<(String, String)> {
+            props.into_iter().map(|p| (p.key, p.value)).collect()
+        };
+        let owned = |v: &[(&str, &str)]| -> Vec<(String, String)> {
+            v.iter()
+                .map(|&(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        assert_eq!(
+            owned(&[("a", "1"), ("b", "2")]),
+            pairs(parse_nested_value("a=1;b=2", ';', '='))
+        );
+        assert_eq!(
+            owned(&[("a", "1;2"), ("b", "x=y"), ("c", "")]),
+            pairs(parse_nested_value(r"a=1\;2 ; b = x=y;; c ;", ';', '='))
+        );
+        assert_eq!(
+            owned(&[("k=1", " v\t")]),
+            pairs(parse_nested_value(r"k\=1: \ v\t", ',', ':'))
+        );
+
+        assert_eq!(
+            owned(&[("caf\u{e9}", "\u{1f600};"), ("b", r"\u12")]),
+            pairs(parse_nested_value(
+                r"caf\u00e9=\ud83d\ude00\u003b;b=\u12",
+                ';',
+                '='
+            ))
+        );
+
+        // escaped in a file, the separator reaches the nested value escaped
+        let parsed = parse(br"jdbc.props=user=sa;password=a\\;b").unwrap();
+        assert_eq!(
+            owned(&[("user", "sa"), ("password", "a;b")]),
+            pairs(parse_nested_value(&parsed[0].value, ';', '='))
+        );
+    }
 }
@@ -0,0 +1,161 @@
+//! Conversion between byte offsets and line and column positions
+
+/// An index of where each line of an input starts, for converting between
+/// byte offsets and `(line, column)` positions. Built with [`line_index`].
+///
+/// Lines and columns are counted from 1, with columns counted in bytes, as in
+/// [`ParseError`](crate::ParseError). Any of `"\r\n"`, `"\r"` or `"\n"` end a
+/// line, and belong to the line they end.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineIndex {
+    /// The offset each line starts at
+    starts: Vec<usize>,
+    /// The offset each line's break starts at, or the length of the input for
+    /// the last line
+    ends: Vec<usize>,
+    /// The length of the input
+    len: usize,
+}
+
+impl LineIndex {
+    /// Returns the position of a byte offset. Offsets past the end of the
+    /// input are treated as the end of the input.
+    pub fn offset_to_pos(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.len);
+        // the first line always starts at 0, so there is always a preceding line
+        let line = self.starts.partition_point(|&start| start <= offset);
+        (line, offset - self.starts[line - 1] + 1)
+    }
+
+    /// Returns the byte offset of a position. Columns past the end of a line
+    /// are treated as the start of its line break, or the end of the input on
+    /// the last line, and lines past the last line as the end of the input.
+    pub fn pos_to_offset(&self, line: usize, col: usize) -> usize {
+        let line = line.max(1) - 1;
+        match self.starts.get(line) {
+            Some(start) => {
+                let offset = start + col.max(1) - 1;
+                let next = self.starts.get(line + 1).copied().unwrap_or(self.len);
+                if offset < next {
+                    offset
+                } else {
+                    self.ends[line]
+                }
+            }
+            None => self.len,
+        }
+    }
+
+    /// Returns the number of lines in the input. An empty input has one line
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+}
+
+/// Builds a [`LineIndex`] of the input in a single pass.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let index = line_index(b"a=1\r\nb=2");
+/// assert_eq!((2, 3), index.offset_to_pos(7));
+/// assert_eq!(7, index.pos_to_offset(2, 3));
+/// ```
+pub fn line_index(input: &[u8]) -> LineIndex {
+    let mut starts = vec![0];
+    let mut ends = Vec::new();
+    for end in line_ends(input) {
+        let crlf = end >= 2 && &input[end - 2..end] == b"\r\n";
+        ends.push(end - if crlf { 2 } else { 1 });
+        starts.push(end);
+    }
+    ends.push(input.len());
+    LineIndex {
+        starts,
+        ends,
+        len: input.len(),
+    }
+}
+
+/// Returns the offset following each line break in `input`, in order. Any of
+/// `"\r\n"`, `"\r"` or `"\n"` is a single break
+pub(crate) fn line_ends(input: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    input
+        .iter()
+        .enumerate()
+        .filter(move |&(i, &b)| {
+            #[cfg(test)]
+            SCANNED.with(|scanned| scanned.set(scanned.get() + 1));
+            b == b'\n' || (b == b'\r' && input.get(i + 1) != Some(&b'\n'))
+        })
+        .map(|(i, _)| i + 1)
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of bytes examined by [`line_ends`] on this thread, for tests
+    /// checking that positions are computed in linear time
+    pub(crate) static SCANNED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Runs `f`, returning the number of bytes [`line_ends`] examined meanwhile
+#[cfg(test)]
+pub(crate) fn count_scanned(f: impl FnOnce()) -> usize {
+    SCANNED.with(|scanned| scanned.set(0));
+    f();
+    SCANNED.with(|scanned| scanned.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crlf_boundaries() {
+        let input = b"a=1\r\nb=2\rc=3\n\nd";
+        let index = line_index(input);
+        assert_eq!(5, index.line_count());
+        assert_eq!((1, 4), index.offset_to_pos(3));
+        assert_eq!((1, 5), index.offset_to_pos(4));
+        assert_eq!((2, 1), index.offset_to_pos(5));
+        assert_eq!((2, 4), index.offset_to_pos(8));
+        assert_eq!((3, 1), index.offset_to_pos(9));
+        assert_eq!((4, 1), index.offset_to_pos(13));
+        for offset in 0..=input.len() {
+            let (line, col) = index.offset_to_pos(offset);
+            assert_eq!(offset, index.pos_to_offset(line, col));
+        }
+    }
+
+    #[test]
+    fn test_last_line() {
+        let index = line_index(b"a=1\nb=2");
+        assert_eq!((2, 1), index.offset_to_pos(4));
+        assert_eq!((2, 4), index.offset_to_pos(7));
+        assert_eq!((2, 4), index.offset_to_pos(100));
+        assert_eq!(7, index.pos_to_offset(2, 100));
+        assert_eq!(7, index.pos_to_offset(3, 1));
+
+        let index = line_index(b"a=1\n");
+        assert_eq!(2, index.line_count());
+        assert_eq!((2, 1), index.offset_to_pos(4));
+        assert_eq!((1, 1), line_index(b"").offset_to_pos(0));
+    }
+
+    #[test]
+    fn test_long_column() {
+        let index = line_index(b"a=1\nb=2");
+        assert_eq!(3, index.pos_to_offset(1, 6));
+        assert_eq!(3, index.pos_to_offset(1, 4));
+        assert_eq!(7, index.pos_to_offset(2, 6));
+
+        let index = line_index(b"a=1\r\nbb=2\r\nc\rd");
+        assert_eq!(3, index.pos_to_offset(1, 100));
+        assert_eq!(4, index.pos_to_offset(1, 5));
+        assert_eq!(10, index.pos_to_offset(2, 6));
+        assert_eq!(9, index.pos_to_offset(2, 7));
+        assert_eq!(9, index.pos_to_offset(2, 100));
+        assert_eq!(12, index.pos_to_offset(3, 3));
+        assert_eq!(14, index.pos_to_offset(4, 3));
+    }
+}
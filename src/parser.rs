@@ -1,15 +1,23 @@
 //! A nom parser for Java properties files
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_till};
-use nom::combinator::{complete, eof, opt, value};
+use nom::bytes::complete::{tag, take_till, take_while_m_n};
+use nom::combinator::{
+    complete, consumed, eof, map, map_opt, map_res, not, opt, peek, recognize, value,
+};
 
 use nom::character::complete::{none_of, one_of};
 use nom::multi::{many0, many1, many_till, separated_list0, separated_list1};
+use nom::sequence::{pair, preceded};
 
+use crate::{line_index, ParseError};
+use nom::error::ErrorKind;
 use nom::IResult;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::fmt;
 
 /// A property representing a parsed configuration key-value pair.
-#[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd)]
 pub struct Property {
     /// A string representing the identifier for a particular property
     pub key: String,
@@ -17,12 +25,144 @@ pub struct Property {
     pub value: String,
 }
 
-/// Consumes a sequence of spaces, tabs, and form feeds ("\f")
-fn consume_whitespaces(input: &[u8]) -> IResult<&[u8], ()> {
-    let (input, _) = many0(one_of(" \t\u{c}"))(input)?;
-    Ok((input, ()))
+impl Property {
+    /// Returns whether the value is empty, as for `key=`. A value holding only
+    /// whitespace, as for `key=\ `, is not empty. See [`Property::value_trimmed`]
+    pub fn is_empty_value(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    /// Returns the value with leading and trailing ASCII whitespace removed
+    ///
+    /// ```
+    /// use props_rs::*;
+    ///
+    /// let parsed = parse(b"key=value  ").unwrap();
+    /// assert_eq!("value", parsed[0].value_trimmed());
+    /// ```
+    pub fn value_trimmed(&self) -> &str {
+        self.value.trim_matches(|c: char| c.is_ascii_whitespace())
+    }
+
+    /// Compares two properties by their keys alone, for sorting or
+    /// deduplicating by key with e.g. [`slice::sort_by`] or
+    /// [`Vec::dedup_by`].
+    ///
+    /// ```
+    /// use props_rs::*;
+    ///
+    /// let mut props = parse(b"b=1\na=2\nb=0").unwrap();
+    /// props.sort_by(Property::cmp_by_key);
+    /// let values: Vec<&str> = props.iter().map(|p| p.value.as_str()).collect();
+    /// // the sort is stable, so duplicate keys keep their order
+    /// assert_eq!(vec!["2", "1", "0"], values);
+    /// ```
+    pub fn cmp_by_key(a: &Property, b: &Property) -> Ordering {
+        a.key.cmp(&b.key)
+    }
+
+    /// Compares two properties by their keys, then by their values when the
+    /// keys are equal.
+    pub fn cmp_by_key_value(a: &Property, b: &Property) -> Ordering {
+        Property::cmp_by_key(a, b).then_with(|| a.value.cmp(&b.value))
+    }
 }
 
+/// An event describing a decision made by the parser, passed to
+/// [`ParseOptions::trace`]. Offsets are in bytes from the start of the input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// Started parsing a key-value line
+    EnteredKvLine {
+        /// The offset the line starts at
+        offset: usize,
+    },
+    /// Joined a line onto the next with a line continuation
+    ContinuationJoined {
+        /// The offset of the continuation's backslash
+        offset: usize,
+    },
+    /// Skipped a comment line
+    CommentSkipped {
+        /// The offset the comment line starts at
+        offset: usize,
+    },
+    /// Skipped a blank line
+    BlankSkipped {
+        /// The offset the blank line starts at
+        offset: usize,
+    },
+}
+
+/// Where a key which is not followed directly by a separator ends.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyBoundary {
+    /// The key ends at the first unescaped whitespace, `=` or `:`, as in the
+    /// Java properties format
+    #[default]
+    FirstWhitespaceOrSep,
+    /// The key ends at the first unescaped `=` or `:`, so `my key = v` has the
+    /// key `my key`. Whitespace before the separator is not part of the key,
+    /// and a line without a separator is read entirely as a key
+    FirstSep,
+}
+
+/// Options which change how a properties file is parsed. The defaults follow
+/// the Java properties format.
+#[derive(Default)]
+pub struct ParseOptions<'a> {
+    /// Treat an unescaped `#` or `!` which follows whitespace within a value as
+    /// the start of a comment running to the end of the line. This is not part
+    /// of the Java format, where such characters are part of the value.
+    pub inline_comments: bool,
+    /// Where a key ends when it contains unescaped whitespace
+    pub key_ends_at: KeyBoundary,
+    /// Replace each `\r\n` and lone `\r` in a value with `\n` after decoding
+    /// escapes, so `a\r\nb` is read as `"a\nb"`. Carriage returns within a
+    /// value can only come from escapes
+    pub normalize_value_crlf: bool,
+    /// Require every `=` or `:` separator to match the first one in the input,
+    /// failing with [`ParseError::MixedSeparator`] otherwise. Properties
+    /// separated only by whitespace are always accepted
+    pub consistent_separator: bool,
+    /// Only treat a line as a comment when its marker is the first character
+    /// of the line. An indented `# x` is then read as a property with the key
+    /// `#`
+    pub comments_must_be_at_col0: bool,
+    /// Fail with [`ParseError::DoubleSeparator`] when a separator is followed
+    /// by another, as in `key==value`, which is usually a typo. Java reads the
+    /// second separator as the start of the value. An empty value, as in
+    /// `key=`, is still accepted
+    pub reject_double_separator: bool,
+    /// The characters treated as whitespace around keys, separators and
+    /// values, and within blank lines. Defaults to space, tab and form feed
+    /// when `None`. Each byte of the input is compared as an ISO 8859-1
+    /// character, so e.g. `'\u{a0}'` matches a non-breaking space
+    pub whitespace_chars: Option<&'a [char]>,
+    /// A callback which is passed a [`TraceEvent`] for each decision the parser
+    /// makes, for debugging files which do not parse as expected.
+    pub trace: Option<Box<dyn FnMut(TraceEvent)>>,
+}
+
+impl fmt::Debug for ParseOptions<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParseOptions")
+            .field("inline_comments", &self.inline_comments)
+            .field("key_ends_at", &self.key_ends_at)
+            .field("normalize_value_crlf", &self.normalize_value_crlf)
+            .field("consistent_separator", &self.consistent_separator)
+            .field("comments_must_be_at_col0", &self.comments_must_be_at_col0)
+            .field("reject_double_separator", &self.reject_double_separator)
+            .field("whitespace_chars", &self.whitespace_chars)
+            .field("trace", &self.trace.as_ref().map(|_| "FnMut(TraceEvent)"))
+            .finish()
+    }
+}
+
+/// The whitespace characters of the Java properties format: spaces, tabs and
+/// form feeds ("\f")
+const WHITESPACE: &[char] = &[' ', '\t', '\u{c}'];
+
 /// Consumes a single EOL of "\r\n", "\r" or "\n"
 fn consume_eol(input: &[u8]) -> IResult<&[u8], ()> {
     let (input, _) = alt((complete(tag("\r\n")), tag("\r"), tag("\n")))(input)?;
@@ -34,15 +174,8 @@ fn consume_eol_or_eof(input: &[u8]) -> IResult<&[u8], ()> {
     alt((value((), eof), consume_eol))(input)
 }
 
-/// Consumes a single blank line
-fn blank_line(input: &[u8]) -> IResult<&[u8], ()> {
-    let (input, _) = consume_whitespaces(input)?;
-    consume_eol_or_eof(input)
-}
-
-/// Consumes a line with a comment
-fn comment_line(input: &[u8]) -> IResult<&[u8], ()> {
-    let (input, _) = consume_whitespaces(input)?;
+/// Consumes a line with a comment whose marker is at the start of the line
+fn unindented_comment_line(input: &[u8]) -> IResult<&[u8], ()> {
     let (input, _) = one_of("#!")(input)?;
     let (input, _) = take_till(eol)(input)?;
     consume_eol_or_eof(input)
@@ -54,104 +187,680 @@ fn eol(c: u8) -> bool {
     c as char == '\r' || c as char == '\n'
 }
 
-/// Consumes a single line escape and any whitespaces after it
-fn consume_line(input: &[u8]) -> IResult<&[u8], ()> {
-    let (input, _) = tag(r"\")(input)?;
-    let (input, _) = consume_eol(input)?;
-    let (input, _) = consume_whitespaces(input)?;
-    Ok((input, ()))
+/// Consumes a comment from its marker up to the end of the line, returning its text
+fn inline_comment(input: &[u8]) -> IResult<&[u8], String> {
+    let (input, text) = recognize(pair(one_of("#!"), take_till(eol)))(input)?;
+    Ok((input, text.iter().map(|&b| b as char).collect()))
 }
 
-/// Consumes a set of alternating lines and whiespaces. Stopping once there is no more alternating
-fn consume_whitespaces_and_lines(input: &[u8]) -> IResult<&[u8], ()> {
-    let (input, _) = separated_list0(many1(consume_line), consume_whitespaces)(input)?;
-    Ok((input, ()))
+/// Consumes a character which exists in a value
+fn char_in_value(input: &[u8]) -> IResult<&[u8], char> {
+    none_of("\n\r\\")(input)
 }
 
-/// Consumes a character that exists in a key
-fn char_in_key(input: &[u8]) -> IResult<&[u8], char> {
-    none_of(":=\n\r \t\u{c}\\")(input)
+/// The single character escapes recognized in keys and values, as pairs of the
+/// character following the backslash and the character the escape represents.
+///
+/// A backslash before any other character is dropped and the character is
+/// taken literally, except for `u` which begins a `\uXXXX` Unicode escape.
+pub const RECOGNIZED_ESCAPES: &[(char, char)] = &[
+    ('t', '\t'),
+    ('n', '\n'),
+    ('f', '\u{c}'),
+    ('r', '\r'),
+    ('\\', '\\'),
+];
+
+/// Consumes the 4 hex digits of a `\u` escape, following the `u`, returning
+/// the UTF-16 code unit they encode
+fn utf16_unit(input: &[u8]) -> IResult<&[u8], u16> {
+    map_res(take_while_m_n(4, 4, |b: u8| b.is_ascii_hexdigit()), |hex| {
+        // the digits are ASCII, so the conversion to str always succeeds
+        u16::from_str_radix(std::str::from_utf8(hex).unwrap_or_default(), 16)
+    })(input)
 }
 
-/// Consumes a character which exists in a value
-fn char_in_value(input: &[u8]) -> IResult<&[u8], char> {
-    none_of("\n\r\\")(input)
+/// Consumes a `\u` escape following its backslash, along with the low
+/// surrogate escape which must follow a high surrogate
+pub(crate) fn unicode_escape(input: &[u8]) -> IResult<&[u8], char> {
+    let start = input;
+    let (input, unit) = preceded(tag("u"), utf16_unit)(input)?;
+    if (0xD800..0xDC00).contains(&unit) {
+        return map_opt(preceded(tag(r"\u"), utf16_unit), |low| {
+            char::decode_utf16([unit, low]).next()?.ok()
+        })(input);
+    }
+    match char::from_u32(u32::from(unit)) {
+        Some(c) => Ok((input, c)),
+        // a low surrogate without a high surrogate before it
+        None => Err(nom::Err::Error(nom::error::Error::new(
+            start,
+            ErrorKind::Char,
+        ))),
+    }
 }
 
 /// matches a single character and returns its escaped equivalent e.g. `'t' -> '\t'`
-fn escaped_char_to_char(v: char) -> char {
-    match v {
-        't' => '\t',
-        'n' => '\n',
-        'f' => '\u{c}',
-        'r' => '\r',
-        '\\' => '\\',
-        _ => v,
-    }
+pub(crate) fn escaped_char_to_char(v: char) -> char {
+    RECOGNIZED_ESCAPES
+        .iter()
+        .find(|&&(escaped, _)| escaped == v)
+        .map_or(v, |&(_, c)| c)
 }
 
-/// consumes an escaped character in a key or value
-fn escape_in_key_or_value(input: &[u8]) -> IResult<&[u8], char> {
-    let (input, _) = tag(r"\")(input)?;
-    let (input, c) = none_of("u\r\n")(input)?;
-    Ok((input, escaped_char_to_char(c)))
+/// The kind of a character escape sequence found in a key or value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscapeKind {
+    /// `\t`, a tab
+    Tab,
+    /// `\n`, a newline
+    Newline,
+    /// `\f`, a form feed
+    FormFeed,
+    /// `\r`, a carriage return
+    CarriageReturn,
+    /// `\\`, a backslash
+    Backslash,
+    /// `\uXXXX`, a UTF-16 code unit, or two when they encode a surrogate pair
+    Unicode(char),
+    /// A backslash before any other character, which is taken literally e.g.
+    /// `\:` or `\ `
+    Other(char),
 }
 
-/// consumes a character in a key
-fn one_char_in_key(input: &[u8]) -> IResult<&[u8], char> {
-    alt((escape_in_key_or_value, char_in_key))(input)
+impl EscapeKind {
+    /// Returns the kind of the escape for the character following a backslash
+    fn from_escaped(c: char) -> Self {
+        match c {
+            't' => EscapeKind::Tab,
+            'n' => EscapeKind::Newline,
+            'f' => EscapeKind::FormFeed,
+            'r' => EscapeKind::CarriageReturn,
+            '\\' => EscapeKind::Backslash,
+            _ => EscapeKind::Other(c),
+        }
+    }
 }
 
-/// consumes a character in a value
-fn one_char_in_value(input: &[u8]) -> IResult<&[u8], char> {
-    alt((escape_in_key_or_value, char_in_value))(input)
+/// The escapes found within a single property, as byte offsets of the
+/// backslash from the start of the input.
+type Escapes = Vec<(usize, EscapeKind)>;
+
+/// A property along with the character escapes found within its key and value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EscapedProperty {
+    /// The parsed property
+    pub property: Property,
+    /// Every escape in the property, in order, along with the byte offset of
+    /// its backslash from the start of the input
+    pub escapes: Vec<(usize, EscapeKind)>,
 }
 
-/// Consumes and returns a `String` representing the key to a property.
-fn consume_key(input: &[u8]) -> IResult<&[u8], String> {
-    // use many1(consume_line) because many0 always returns true and causes a separated list error.
-    let (input, chars) = separated_list1(many1(consume_line), many1(one_char_in_key))(input)?;
-    Ok((input, chars.into_iter().flatten().collect::<String>()))
+/// A trace callback borrowed from [`ParseOptions::trace`] for a single parse
+type Trace<'o> = &'o mut (dyn FnMut(TraceEvent) + 'static);
+
+/// State shared between the parsers while consuming a single input
+struct Context<'o> {
+    /// The options the input is parsed with
+    opts: &'o ParseOptions<'o>,
+    /// The length of the full input, used to compute offsets of the remaining input
+    len: usize,
+    /// The escapes within the current property, if they are being recorded
+    escapes: Option<RefCell<Escapes>>,
+    /// The trace callback, if tracing is enabled
+    trace: Option<RefCell<Trace<'o>>>,
+    /// The offset of the last continuation traced. Continuations may be consumed
+    /// again after backtracking and should only be traced once
+    last_continuation: Cell<Option<usize>>,
+    /// The first separator in the input, when a consistent separator is required
+    separator: Cell<Option<char>>,
+    /// The reason a property was rejected by one of the stricter options
+    rejected: Cell<Option<Rejection>>,
 }
 
-/// Consumes and returns a `String` representing the value of a property.
-fn consume_value(input: &[u8]) -> IResult<&[u8], String> {
-    // use many1(consume_line) because many0 always returns true and causes a separated list error.
-    let (input, chars) = separated_list0(many1(consume_line), many0(one_char_in_value))(input)?;
-    Ok((input, chars.into_iter().flatten().collect::<String>()))
+/// The reason a property was rejected by one of the stricter options, kept
+/// so that the failure can be reported with its cause
+#[derive(Clone, Copy, Debug)]
+enum Rejection {
+    /// The offset of a separator which differed from the first, along with the
+    /// first separator and the differing one
+    MixedSeparator(usize, char, char),
+    /// The offset of a separator directly following another
+    DoubleSeparator(usize),
 }
 
-/// Consumes an entire line (or set of lines) representing a key-value property
-fn kv_line(input: &[u8]) -> IResult<&[u8], Property> {
-    let (input, _) = consume_whitespaces_and_lines(input)?;
-    let (input, key) = consume_key(input)?;
-    let (input, _) = consume_whitespaces_and_lines(input)?;
-    let (input, _) = opt(complete(one_of(":=")))(input)?;
-    let (input, _) = consume_whitespaces_and_lines(input)?;
-    let (input, value) = consume_value(input)?;
-    let (input, _) = consume_eol_or_eof(input)?;
-    Ok((input, Property { key, value }))
+impl Rejection {
+    /// Converts the rejection into an error within `input`
+    fn into_error(self, input: &[u8]) -> ParseError {
+        match self {
+            Rejection::MixedSeparator(offset, expected, found) => {
+                ParseError::mixed_separator(input, offset, expected, found)
+            }
+            Rejection::DoubleSeparator(offset) => ParseError::double_separator(input, offset),
+        }
+    }
+
+    /// Converts the rejection into an error at an already known position
+    fn into_error_at(self, (line, column): (usize, usize)) -> ParseError {
+        match self {
+            Rejection::MixedSeparator(_, expected, found) => ParseError::MixedSeparator {
+                line,
+                column,
+                expected,
+                found,
+            },
+            Rejection::DoubleSeparator(_) => ParseError::DoubleSeparator { line, column },
+        }
+    }
+
+    /// Returns the offset the rejection occurred at
+    fn offset(self) -> usize {
+        match self {
+            Rejection::MixedSeparator(offset, ..) | Rejection::DoubleSeparator(offset) => offset,
+        }
+    }
+}
+
+impl<'o> Context<'o> {
+    /// Creates a context for parsing the given input
+    fn new(input: &[u8], opts: &'o ParseOptions<'o>) -> Self {
+        Context {
+            opts,
+            len: input.len(),
+            escapes: None,
+            trace: None,
+            last_continuation: Cell::new(None),
+            separator: Cell::new(None),
+            rejected: Cell::new(None),
+        }
+    }
+
+    /// Enables recording of escapes
+    fn recording(mut self) -> Self {
+        self.escapes = Some(RefCell::new(Vec::new()));
+        self
+    }
+
+    /// Enables tracing with the given callback, if there is one
+    fn tracing(mut self, trace: Option<Trace<'o>>) -> Self {
+        self.trace = trace.map(RefCell::new);
+        self
+    }
+
+    /// Returns the offset of the remaining input from the start of the input
+    fn offset(&self, input: &[u8]) -> usize {
+        self.len - input.len()
+    }
+
+    /// Passes an event to the trace callback. The event is only built when
+    /// tracing is enabled
+    fn emit(&self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(trace) = &self.trace {
+            (trace.borrow_mut())(event());
+        }
+    }
+
+    /// Checks the separator at the start of `input` against the first separator
+    /// when a consistent separator is required, failing if they differ
+    fn check_separator<'a>(&self, input: &'a [u8], sep: char) -> IResult<&'a [u8], ()> {
+        if !self.opts.consistent_separator {
+            return Ok((input, ()));
+        }
+        match self.separator.get() {
+            Some(expected) if expected != sep => {
+                let offset = self.offset(input);
+                return self.reject(input, Rejection::MixedSeparator(offset, expected, sep));
+            }
+            Some(_) => {}
+            None => self.separator.set(Some(sep)),
+        }
+        Ok((input, ()))
+    }
+
+    /// Checks that a value does not start with another separator when double
+    /// separators are rejected, failing if it does
+    fn check_double_separator<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        match input.first() {
+            Some(b'=') | Some(b':') if self.opts.reject_double_separator => {
+                let offset = self.offset(input);
+                self.reject(input, Rejection::DoubleSeparator(offset))
+            }
+            _ => Ok((input, ())),
+        }
+    }
+
+    /// Records why the property at `input` was rejected, and fails without
+    /// trying any alternatives
+    fn reject<'a>(&self, input: &'a [u8], rejection: Rejection) -> IResult<&'a [u8], ()> {
+        self.rejected.set(Some(rejection));
+        Err(nom::Err::Failure(nom::error::Error::new(
+            input,
+            ErrorKind::Verify,
+        )))
+    }
+
+    /// Returns whether a byte (as a character) is whitespace
+    fn is_whitespace(&self, b: u8) -> bool {
+        self.opts
+            .whitespace_chars
+            .unwrap_or(WHITESPACE)
+            .contains(&(b as char))
+    }
+
+    /// Consumes a single whitespace character
+    fn one_whitespace<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], char> {
+        match input.first() {
+            Some(&b) if self.is_whitespace(b) => Ok((&input[1..], b as char)),
+            _ => Err(nom::Err::Error(nom::error::Error::new(
+                input,
+                ErrorKind::OneOf,
+            ))),
+        }
+    }
+
+    /// Consumes a sequence of whitespace characters
+    fn consume_whitespaces<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        let (input, _) = many0(|i| self.one_whitespace(i))(input)?;
+        Ok((input, ()))
+    }
+
+    /// Consumes a character that exists in a key
+    fn char_in_key<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], char> {
+        match input.first() {
+            Some(&b) if self.is_whitespace(b) => Err(nom::Err::Error(nom::error::Error::new(
+                input,
+                ErrorKind::NoneOf,
+            ))),
+            _ => none_of(":=\n\r\\")(input),
+        }
+    }
+
+    /// Consumes an unescaped whitespace character which is followed, after any
+    /// more whitespace, by another character of the key
+    fn whitespace_in_key<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], char> {
+        let (input, c) = self.one_whitespace(input)?;
+        let (input, _) = peek(preceded(
+            |i| self.consume_whitespaces(i),
+            // a backslash ending the line is a continuation rather than an escape
+            alt((
+                |i| self.char_in_key(i),
+                value('\\', pair(tag(r"\"), none_of("\r\n"))),
+            )),
+        ))(input)?;
+        Ok((input, c))
+    }
+
+    /// Consumes whitespace followed by a comment up to the end of the line
+    fn trailing_comment<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], String> {
+        preceded(many1(|i| self.one_whitespace(i)), inline_comment)(input)
+    }
+
+    /// Consumes a single blank line
+    fn blank_line<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        let (rest, _) = self.consume_whitespaces(input)?;
+        let (rest, _) = consume_eol_or_eof(rest)?;
+        self.emit(|| TraceEvent::BlankSkipped {
+            offset: self.offset(input),
+        });
+        Ok((rest, ()))
+    }
+
+    /// Consumes a line with a comment
+    fn comment_line<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        let (rest, _) = if self.opts.comments_must_be_at_col0 {
+            unindented_comment_line(input)?
+        } else {
+            preceded(|i| self.consume_whitespaces(i), unindented_comment_line)(input)?
+        };
+        self.emit(|| TraceEvent::CommentSkipped {
+            offset: self.offset(input),
+        });
+        Ok((rest, ()))
+    }
+
+    /// Consumes a single line escape and any whitespaces after it
+    fn consume_line<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        let start = input;
+        let (input, _) = tag(r"\")(input)?;
+        let (input, _) = consume_eol(input)?;
+        let (input, _) = self.consume_whitespaces(input)?;
+        if self.trace.is_some() {
+            let offset = self.offset(start);
+            if self.last_continuation.get() < Some(offset) {
+                self.last_continuation.set(Some(offset));
+                self.emit(|| TraceEvent::ContinuationJoined { offset });
+            }
+        }
+        Ok((input, ()))
+    }
+
+    /// Consumes a set of alternating lines and whiespaces. Stopping once there is no more alternating
+    fn consume_whitespaces_and_lines<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        let (input, _) = separated_list0(many1(|i| self.consume_line(i)), |i| {
+            self.consume_whitespaces(i)
+        })(input)?;
+        Ok((input, ()))
+    }
+
+    /// Returns the recorded escapes, resetting them for the next property
+    fn take_escapes(&self) -> Escapes {
+        self.escapes
+            .as_ref()
+            .map(|e| e.replace(Vec::new()))
+            .unwrap_or_default()
+    }
+
+    /// consumes an escaped character in a key or value
+    fn escape_in_key_or_value<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], char> {
+        let start = input;
+        let (input, _) = tag(r"\")(input)?;
+        let (input, (kind, c)) = alt((
+            map(unicode_escape, |c| (EscapeKind::Unicode(c), c)),
+            map(none_of("u\r\n"), |c| {
+                (EscapeKind::from_escaped(c), escaped_char_to_char(c))
+            }),
+        ))(input)?;
+        if let Some(escapes) = &self.escapes {
+            let offset = self.len - start.len();
+            escapes.borrow_mut().push((offset, kind));
+        }
+        Ok((input, c))
+    }
+
+    /// consumes a character in a key
+    fn one_char_in_key<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], char> {
+        match self.opts.key_ends_at {
+            KeyBoundary::FirstWhitespaceOrSep => {
+                alt((|i| self.escape_in_key_or_value(i), |i| self.char_in_key(i)))(input)
+            }
+            KeyBoundary::FirstSep => alt((
+                |i| self.escape_in_key_or_value(i),
+                |i| self.char_in_key(i),
+                |i| self.whitespace_in_key(i),
+            ))(input),
+        }
+    }
+
+    /// consumes a character in a value
+    fn one_char_in_value<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], char> {
+        if self.opts.inline_comments {
+            not(|i| self.trailing_comment(i))(input)?;
+        }
+        alt((|i| self.escape_in_key_or_value(i), char_in_value))(input)
+    }
+
+    /// Consumes and returns a `String` representing the key to a property.
+    fn consume_key<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], String> {
+        // use many1(consume_line) because many0 always returns true and causes a separated list error.
+        let (input, chars) = separated_list1(
+            many1(|i| self.consume_line(i)),
+            many1(|i| self.one_char_in_key(i)),
+        )(input)?;
+        Ok((input, chars.into_iter().flatten().collect::<String>()))
+    }
+
+    /// Consumes and returns a `String` representing the value of a property.
+    ///
+    /// A line continuation whose line break is followed by a blank line or the
+    /// end of the input joins nothing onto the value, so `value\` followed by
+    /// a final line break is `value`. A backslash which is the very last byte
+    /// of the input is not a continuation, and fails to parse.
+    fn consume_value<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], String> {
+        // use many1(consume_line) because many0 always returns true and causes a separated list error.
+        let (input, chars) = separated_list0(
+            many1(|i| self.consume_line(i)),
+            many0(|i| self.one_char_in_value(i)),
+        )(input)?;
+        Ok((input, chars.into_iter().flatten().collect::<String>()))
+    }
+
+    /// Consumes a value like [`Context::consume_value`] which also ends before
+    /// any unescaped character in `terminators`
+    fn consume_value_until<'a>(
+        &self,
+        input: &'a [u8],
+        terminators: &[char],
+    ) -> IResult<&'a [u8], String> {
+        let one_char = |i: &'a [u8]| match i.first() {
+            Some(&b) if terminators.contains(&(b as char)) => Err(nom::Err::Error(
+                nom::error::Error::new(i, ErrorKind::NoneOf),
+            )),
+            _ => self.one_char_in_value(i),
+        };
+        let (input, chars) =
+            separated_list0(many1(|i| self.consume_line(i)), many0(one_char))(input)?;
+        Ok((input, chars.into_iter().flatten().collect::<String>()))
+    }
+
+    /// Consumes an entire line (or set of lines) representing a key-value property
+    fn kv_line<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], Property> {
+        let (input, (prop, _)) = self.kv_line_with_comment(input)?;
+        Ok((input, prop))
+    }
+
+    /// Consumes a key-value property along with its trailing comment, which is
+    /// only recognized when inline comments are enabled.
+    fn kv_line_with_comment<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> IResult<&'a [u8], (Property, Option<String>)> {
+        self.emit(|| TraceEvent::EnteredKvLine {
+            offset: self.offset(input),
+        });
+        let (input, _) = self.consume_whitespaces_and_lines(input)?;
+        let (input, key) = self.consume_key(input)?;
+        let (input, before_sep) = recognize(|i| self.consume_whitespaces_and_lines(i))(input)?;
+        let at_sep = input;
+        let (input, sep) = opt(complete(one_of(":=")))(input)?;
+        if let Some(sep) = sep {
+            self.check_separator(at_sep, sep)?;
+        }
+        let (input, after_sep) = recognize(|i| self.consume_whitespaces_and_lines(i))(input)?;
+        if sep.is_some() {
+            self.check_double_separator(input)?;
+        }
+        // a comment directly following the separator leaves the value empty
+        let spaced = !after_sep.is_empty() || (sep.is_none() && !before_sep.is_empty());
+        let (input, leading) = if self.opts.inline_comments && spaced {
+            opt(inline_comment)(input)?
+        } else {
+            (input, None)
+        };
+        let (input, value, comment) = match leading {
+            Some(comment) => (input, String::new(), Some(comment)),
+            None => {
+                let (input, value) = self.consume_value(input)?;
+                let (input, comment) = if self.opts.inline_comments {
+                    opt(|i| self.trailing_comment(i))(input)?
+                } else {
+                    (input, None)
+                };
+                (input, value, comment)
+            }
+        };
+        let (input, _) = consume_eol_or_eof(input)?;
+        let value = if self.opts.normalize_value_crlf && value.contains('\r') {
+            value.replace("\r\n", "\n").replace('\r', "\n")
+        } else {
+            value
+        };
+        Ok((input, (Property { key, value }, comment)))
+    }
+
+    /// Consumes a key-value property along with the escapes found within it
+    fn kv_line_with_escapes<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], EscapedProperty> {
+        self.take_escapes();
+        let (input, property) = self.kv_line(input)?;
+        let escapes = self.take_escapes();
+        Ok((input, EscapedProperty { property, escapes }))
+    }
+
+    /// The full parser which consumes comments, blanks, and Property lines.
+    fn _fparser<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], ParsedProps<'a>> {
+        many_till(
+            alt((
+                value(None, complete(|i| self.comment_line(i))),
+                value(None, complete(|i| self.blank_line(i))),
+                opt(complete(|i| self.kv_line(i))),
+            )),
+            eof,
+        )(input)
+    }
 }
 
 type ParsedProps<'a> = (Vec<Option<Property>>, &'a [u8]);
 
-/// The full parser which consumes comments, blanks, and Property lines.
-fn _fparser(input: &[u8]) -> IResult<&[u8], ParsedProps> {
-    many_till(
+/// Runs the parser `f` over the whole input with a context which borrows the
+/// trace callback from the options, converting any error into a [`ParseError`]
+fn with_context<'a, T>(
+    input: &'a [u8],
+    opts: &mut ParseOptions,
+    f: impl FnOnce(&Context) -> IResult<&'a [u8], T>,
+) -> Result<T, ParseError> {
+    // the callback is taken out of the options so they can be shared while it is called
+    let mut trace = opts.trace.take();
+    let ctx = Context::new(input, opts).tracing(trace.as_deref_mut());
+    let res = match (f(&ctx), ctx.rejected.get()) {
+        (_, Some(rejection)) => Err(rejection.into_error(input)),
+        (Ok((_, v)), None) => Ok(v),
+        (Err(e), None) => Err(ParseError::from_nom(input, e)),
+    };
+    drop(ctx);
+    opts.trace = trace;
+    res
+}
+
+/// Public parser function
+pub fn parser(input: &[u8]) -> IResult<&[u8], Vec<Property>> {
+    let opts = ParseOptions::default();
+    let (input, props) = Context::new(input, &opts)._fparser(input)?;
+    Ok((input, props.0.into_iter().flatten().collect()))
+}
+
+/// Parser function which consumes the whole input according to the given options
+pub(crate) fn parser_with_options(
+    input: &[u8],
+    opts: &mut ParseOptions,
+) -> Result<Vec<Property>, ParseError> {
+    let props = with_context(input, opts, |ctx| ctx._fparser(input))?;
+    Ok(props.0.into_iter().flatten().collect())
+}
+
+/// Parser which skips each line which fails to parse rather than failing,
+/// returning the properties which did parse along with an error for every
+/// skipped line
+pub(crate) fn parser_lenient(
+    input: &[u8],
+    opts: &mut ParseOptions,
+) -> (Vec<Property>, Vec<ParseError>) {
+    // positions come from a single index, as there may be an error on every line
+    let index = line_index(input);
+    let mut trace = opts.trace.take();
+    let ctx = Context::new(input, opts).tracing(trace.as_deref_mut());
+    let mut props = Vec::new();
+    let mut errors = Vec::new();
+    let mut rest = input;
+    while !rest.is_empty() {
+        let line = alt((
+            value(None, complete(|i| ctx.comment_line(i))),
+            value(None, complete(|i| ctx.blank_line(i))),
+            map(complete(|i| ctx.kv_line(i)), Some),
+        ))(rest);
+        match line {
+            Ok((remaining, prop)) => {
+                props.extend(prop);
+                rest = remaining;
+            }
+            Err(e) => {
+                // skip to the end of the line the error is on, which may be
+                // a continuation of the line `rest` starts on
+                let at = match &e {
+                    nom::Err::Error(e) | nom::Err::Failure(e) if e.input.len() < rest.len() => {
+                        e.input
+                    }
+                    _ => rest,
+                };
+                let end = at
+                    .iter()
+                    .position(|&b| b == b'\r' || b == b'\n')
+                    .unwrap_or(at.len());
+                rest = consume_eol(&at[end..]).map_or(&at[end..], |(r, _)| r);
+                errors.push(match ctx.rejected.take() {
+                    Some(rejection) => {
+                        rejection.into_error_at(index.offset_to_pos(rejection.offset()))
+                    }
+                    None => {
+                        let (line, column) = index.offset_to_pos(ctx.offset(at));
+                        ParseError::Syntax { line, column }
+                    }
+                });
+            }
+        }
+    }
+    drop(ctx);
+    opts.trace = trace;
+    (props, errors)
+}
+
+/// Parser which returns every property along with the escapes found within it
+pub(crate) fn parser_with_escapes(input: &[u8]) -> IResult<&[u8], Vec<EscapedProperty>> {
+    let opts = ParseOptions::default();
+    let ctx = Context::new(input, &opts).recording();
+    let (input, (props, _)) = many_till(
         alt((
-            value(None, complete(comment_line)),
-            value(None, complete(blank_line)),
-            opt(complete(kv_line)),
+            value(None, complete(|i| ctx.comment_line(i))),
+            value(None, complete(|i| ctx.blank_line(i))),
+            opt(complete(|i| ctx.kv_line_with_escapes(i))),
         )),
         eof,
-    )(input)
+    )(input)?;
+    Ok((input, props.into_iter().flatten().collect()))
 }
 
-/// Public parser function
-pub fn parser(input: &[u8]) -> IResult<&[u8], Vec<Property>> {
-    let (input, props) = _fparser(input)?;
-    let v = props.0.into_iter().flatten().collect();
-    Ok((input, v))
+/// Parser which consumes a single value up to an unescaped terminator, the end
+/// of its line or the end of the input, none of which are consumed
+pub(crate) fn value_until<'a>(input: &'a [u8], terminators: &[char]) -> IResult<&'a [u8], String> {
+    let opts = ParseOptions::default();
+    let (rest, value) = Context::new(input, &opts).consume_value_until(input, terminators)?;
+    match rest.first() {
+        Some(&b) if !eol(b) && !terminators.contains(&(b as char)) => Err(nom::Err::Error(
+            nom::error::Error::new(rest, ErrorKind::Escaped),
+        )),
+        _ => Ok((rest, value)),
+    }
+}
+
+/// A single line (or logical line) of a properties file along with the source
+/// text it was parsed from.
+pub(crate) enum Line<'a> {
+    Blank(&'a [u8]),
+    Comment(&'a [u8]),
+    Property(Property, Option<String>, &'a [u8]),
+}
+
+/// Parser which retains every line of the input, including comments and blanks
+pub(crate) fn lines<'a>(
+    input: &'a [u8],
+    opts: &mut ParseOptions,
+) -> Result<Vec<Line<'a>>, ParseError> {
+    let (lines, _) = with_context(input, opts, |ctx| {
+        many_till(
+            alt((
+                map(consumed(complete(|i| ctx.comment_line(i))), |(raw, _)| {
+                    Line::Comment(raw)
+                }),
+                map(consumed(complete(|i| ctx.blank_line(i))), |(raw, _)| {
+                    Line::Blank(raw)
+                }),
+                map(
+                    consumed(complete(|i| ctx.kv_line_with_comment(i))),
+                    |(raw, (p, comment))| Line::Property(p, comment, raw),
+                ),
+            )),
+            eof,
+        )(input)
+    })?;
+    Ok(lines)
 }
 
 #[cfg(test)]
@@ -159,6 +868,31 @@ mod test {
     use super::*;
     use nom::error::dbg_dmp;
 
+    fn prop(k: &str, v: &str) -> Property {
+        Property {
+            key: String::from(k),
+            value: String::from(v),
+        }
+    }
+
+    // the parsers below run with the default options
+
+    fn consume_key(input: &[u8]) -> IResult<&[u8], String> {
+        Context::new(input, &ParseOptions::default()).consume_key(input)
+    }
+
+    fn consume_value(input: &[u8]) -> IResult<&[u8], String> {
+        Context::new(input, &ParseOptions::default()).consume_value(input)
+    }
+
+    fn kv_line(input: &[u8]) -> IResult<&[u8], Property> {
+        Context::new(input, &ParseOptions::default()).kv_line(input)
+    }
+
+    fn _fparser(input: &[u8]) -> IResult<&[u8], ParsedProps<'_>> {
+        Context::new(input, &ParseOptions::default())._fparser(input)
+    }
+
     macro_rules! assert_done {
         ($t:expr, $v:expr) => {
             assert_eq!($t, Ok((&b""[..], $v)))
@@ -266,8 +1000,7 @@ mod test {
         );
     }
 
-    /// utf-8 not yet implemented
-    #[allow(dead_code)]
+    #[test]
     fn test_utf8_keys() {
         // Unicode esacpes
         assert_done!(
@@ -340,10 +1073,18 @@ mod test {
             consume_value(b"gh\\\n    \\\r    \\\r\nij\\\n\t kl"),
             String::from("ghijkl")
         );
+
+        // A continuation with nothing after it adds nothing to the value
+        assert_done!(consume_value(b"value\\\n"), String::from("value"));
+        assert_done!(consume_value(b"value\\\r\n  "), String::from("value"));
+        assert_done_partial!(
+            consume_value(b"value\\\n\nnext"),
+            String::from("value"),
+            b"\nnext"
+        );
     }
 
-    /// utf-8 not yet implemented
-    #[allow(dead_code)]
+    #[test]
     fn test_utf8_values() {
         // Unicode esacpes
         assert_done!(
@@ -375,6 +1116,18 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_continuation_on_last_line() {
+        assert_done!(parser(b"key=value\\\n"), vec![prop("key", "value")]);
+        assert_done!(
+            parser(b"key=value\\\n\nnext=other\n"),
+            vec![prop("key", "value"), prop("next", "other")]
+        );
+        assert_done!(parser(b"key=value\\\r\n  \t\n"), vec![prop("key", "value")]);
+        // a backslash as the last byte has no line break to continue
+        assert!(crate::parse(b"key=value\\").is_err());
+    }
+
     #[test]
     fn test_full_parse_simple() {
         let prop = br"key.1=value1
@@ -407,4 +1160,266 @@ key.two=value2
         assert_eq!(props[1].key, "key.two");
         assert_eq!(props[1].value, "value2")
     }
+
+    #[test]
+    fn test_escape_offsets() {
+        let input = b"a=1\n  k\\\\ey = v\\tx\\\\\n";
+        let (_, props) = parser_with_escapes(input).unwrap();
+        assert_eq!(2, props.len());
+        assert_eq!("k\\ey", props[1].property.key);
+        assert_eq!("v\tx\\", props[1].property.value);
+        assert_eq!(Vec::<(usize, EscapeKind)>::new(), props[0].escapes);
+        assert_eq!(
+            vec![
+                (7, EscapeKind::Backslash),
+                (15, EscapeKind::Tab),
+                (18, EscapeKind::Backslash)
+            ],
+            props[1].escapes
+        );
+    }
+
+    #[test]
+    fn test_unicode_escapes() {
+        assert_done!(
+            consume_value(br"caf\u00e9 \u20AC \ud83d\ude00"),
+            String::from("caf\u{e9} \u{20ac} \u{1f600}")
+        );
+        assert_done!(
+            kv_line(br"\u006B\u0065y=v"),
+            Property {
+                key: String::from("key"),
+                value: String::from("v"),
+            }
+        );
+        // a surrogate must be part of a pair
+        assert_done_partial!(
+            consume_value(br"a\ud83d b"),
+            String::from("a"),
+            br"\ud83d b"
+        );
+        assert_done_partial!(consume_value(br"a\ude00"), String::from("a"), br"\ude00");
+        assert!(parser(br"key=\u12").is_err());
+
+        let (_, props) = parser_with_escapes(br"k=\u00e9\ud83d\ude00").unwrap();
+        assert_eq!(
+            vec![
+                (2, EscapeKind::Unicode('\u{e9}')),
+                (8, EscapeKind::Unicode('\u{1f600}'))
+            ],
+            props[0].escapes
+        );
+    }
+
+    #[test]
+    fn test_inline_comments() {
+        let opts = ParseOptions {
+            inline_comments: true,
+            ..Default::default()
+        };
+        let ctx = Context::new(b"", &opts);
+        let comment = |c: &str| Some(String::from(c));
+        assert_done!(
+            ctx.kv_line_with_comment(b"key = value # note"),
+            (prop("key", "value"), comment("# note"))
+        );
+        assert_done!(
+            ctx.kv_line_with_comment(b"key = first \\\n  second\t! note \\"),
+            (prop("key", "first second"), comment("! note \\"))
+        );
+        assert_done!(
+            ctx.kv_line_with_comment(b"key = # note"),
+            (prop("key", ""), comment("# note"))
+        );
+        // a marker which does not follow whitespace is part of the value
+        assert_done!(
+            ctx.kv_line_with_comment(b"color=#fff url=a#b"),
+            (prop("color", "#fff url=a#b"), None)
+        );
+        // escaped markers are part of the value
+        assert_done!(
+            ctx.kv_line_with_comment(br"key = a \# b"),
+            (prop("key", "a # b"), None)
+        );
+        // without the option the marker is part of the value
+        assert_done!(kv_line(b"key = value # note"), prop("key", "value # note"));
+    }
+
+    #[test]
+    fn test_value_until() {
+        assert_done_partial!(value_until(b"a b;c", &[';']), String::from("a b"), b";c");
+        assert_done_partial!(value_until(br"a\;b;c", &[';']), String::from("a;b"), b";c");
+        assert_done_partial!(
+            value_until(b"a \\\n  b}", &[';', '}']),
+            String::from("a b"),
+            b"}"
+        );
+        assert_done_partial!(value_until(b"a\nb;", &[';']), String::from("a"), b"\nb;");
+        assert_done!(value_until(b"", &[';']), String::from(""));
+        assert_done_partial!(
+            value_until(br"a\u00e9;", &[';']),
+            String::from("a\u{e9}"),
+            b";"
+        );
+        assert!(value_until(br"a\u12;", &[';']).is_err());
+    }
+
+    #[test]
+    fn test_comments_must_be_at_col0() {
+        let input = b"# top\n   # indented\n\t! tabbed\nkey=value\n";
+        let props = parser_with_options(input, &mut ParseOptions::default()).unwrap();
+        assert_eq!(1, props.len());
+
+        let mut opts = ParseOptions {
+            comments_must_be_at_col0: true,
+            ..Default::default()
+        };
+        let props = parser_with_options(input, &mut opts).unwrap();
+        let pairs: Vec<(&str, &str)> = props
+            .iter()
+            .map(|p| (p.key.as_str(), p.value.as_str()))
+            .collect();
+        assert_eq!(
+            vec![("#", "indented"), ("!", "tabbed"), ("key", "value")],
+            pairs
+        );
+    }
+
+    #[test]
+    fn test_value_introspection() {
+        let (_, empty) = kv_line(b"key=").unwrap();
+        assert!(empty.is_empty_value());
+        assert_eq!("", empty.value_trimmed());
+
+        let (_, blank) = kv_line(br"key=\ \t ").unwrap();
+        assert!(!blank.is_empty_value());
+        assert_eq!("", blank.value_trimmed());
+
+        let (_, normal) = kv_line(b"key = value\x0c ").unwrap();
+        assert!(!normal.is_empty_value());
+        assert_eq!("value", normal.value_trimmed());
+        assert_eq!("value\u{c} ", normal.value);
+    }
+
+    #[test]
+    fn test_whitespace_chars() {
+        let nbsp = b"key\xa0=\xa0\xa0value\n\xa0\n\xa0# comment\nk\xa0v";
+        let mut opts = ParseOptions {
+            whitespace_chars: Some(&[' ', '\t', '\u{c}', '\u{a0}']),
+            ..Default::default()
+        };
+        let props = parser_with_options(nbsp, &mut opts).unwrap();
+        let pairs: Vec<(&str, &str)> = props
+            .iter()
+            .map(|p| (p.key.as_str(), p.value.as_str()))
+            .collect();
+        assert_eq!(vec![("key", "value"), ("k", "v")], pairs);
+
+        // by default a non-breaking space is part of the key or value
+        let (_, props) = parser(b"key\xa0=\xa0value").unwrap();
+        assert_eq!("key\u{a0}", props[0].key);
+        assert_eq!("\u{a0}value", props[0].value);
+
+        // restricting the set makes tabs part of keys
+        let mut opts = ParseOptions {
+            whitespace_chars: Some(&[' ']),
+            ..Default::default()
+        };
+        let props = parser_with_options(b"a\tb = c", &mut opts).unwrap();
+        assert_eq!("a\tb", props[0].key);
+        let props = parser_with_options(b"\t\n", &mut opts).unwrap();
+        assert_eq!("\t", props[0].key);
+    }
+
+    #[test]
+    fn test_property_cmp() {
+        let (a1, a2, b1) = (prop("a", "1"), prop("a", "2"), prop("b", "1"));
+        assert_eq!(Ordering::Equal, Property::cmp_by_key(&a1, &a2));
+        assert_eq!(Ordering::Less, Property::cmp_by_key(&a2, &b1));
+        assert_eq!(Ordering::Less, Property::cmp_by_key_value(&a1, &a2));
+        assert_eq!(Ordering::Greater, Property::cmp_by_key_value(&a2, &a1));
+        assert_eq!(Ordering::Less, Property::cmp_by_key_value(&a2, &b1));
+        assert_eq!(
+            Ordering::Equal,
+            Property::cmp_by_key_value(&a1, &a1.clone())
+        );
+    }
+
+    #[test]
+    fn test_key_ends_at() {
+        let opts = ParseOptions {
+            key_ends_at: KeyBoundary::FirstSep,
+            ..Default::default()
+        };
+        let ctx = Context::new(b"", &opts);
+        assert_done!(ctx.kv_line(b"my key = v"), prop("my key", "v"));
+        assert_done!(ctx.kv_line(b"my \t key:v"), prop("my \t key", "v"));
+        assert_done!(ctx.kv_line(br"my\ key \ = v"), prop("my key  ", "v"));
+        assert_done!(ctx.kv_line(b"my key \\\n  = v"), prop("my key", "v"));
+        assert_done!(
+            ctx.kv_line(b"  no separator here"),
+            prop("no separator here", "")
+        );
+        // the Java format ends the key at the first whitespace
+        assert_done!(kv_line(b"my key = v"), prop("my", "key = v"));
+    }
+
+    #[test]
+    fn test_normalize_value_crlf() {
+        let opts = ParseOptions {
+            normalize_value_crlf: true,
+            ..Default::default()
+        };
+        let ctx = Context::new(b"", &opts);
+        assert_done!(ctx.kv_line(br"key=a\r\nb"), prop("key", "a\nb"));
+        assert_done!(ctx.kv_line(br"key=a\rb\r\r\n"), prop("key", "a\nb\n\n"));
+        assert_done!(ctx.kv_line(br"k\re\ry=a\r"), prop("k\re\ry", "a\n"));
+        assert_done!(kv_line(br"key=a\r\nb"), prop("key", "a\r\nb"));
+    }
+
+    #[test]
+    fn test_trace() {
+        let events = std::rc::Rc::new(RefCell::new(Vec::new()));
+        let sink = events.clone();
+        let mut opts = ParseOptions {
+            trace: Some(Box::new(move |e| sink.borrow_mut().push(e))),
+            ..Default::default()
+        };
+        let input = b"# c\n\nkey\\\n  =a\\\n b\n";
+        let props = parser_with_options(input, &mut opts).unwrap();
+        assert_eq!("ab", props[0].value);
+        assert_eq!(
+            vec![
+                TraceEvent::CommentSkipped { offset: 0 },
+                TraceEvent::BlankSkipped { offset: 4 },
+                TraceEvent::EnteredKvLine { offset: 5 },
+                TraceEvent::ContinuationJoined { offset: 8 },
+                TraceEvent::ContinuationJoined { offset: 14 },
+            ],
+            *events.borrow()
+        );
+        // the callback is handed back and may be used for another parse
+        assert!(opts.trace.is_some());
+    }
+
+    #[test]
+    fn test_recognized_escapes() {
+        assert!(RECOGNIZED_ESCAPES.contains(&('t', '\t')));
+        for &(escaped, c) in RECOGNIZED_ESCAPES {
+            let input = format!("\\{}", escaped);
+            assert_done!(consume_value(input.as_bytes()), c.to_string());
+        }
+    }
+
+    #[test]
+    fn test_escape_kinds_cover_recognized_escapes() {
+        let mut kinds = Vec::new();
+        for &(escaped, _) in RECOGNIZED_ESCAPES {
+            let kind = EscapeKind::from_escaped(escaped);
+            assert_ne!(EscapeKind::Other(escaped), kind);
+            assert!(!kinds.contains(&kind), "{:?} is listed twice", kind);
+            kinds.push(kind);
+        }
+        assert_eq!(EscapeKind::Other(':'), EscapeKind::from_escaped(':'));
+    }
 }
@@ -0,0 +1,625 @@
+//! An ordered set of properties with unique keys
+use crate::{ConfigError, InvalidEnumError, MissingKeyError, Property};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// A set of properties with unique keys which remembers the order keys were
+/// first seen in.
+///
+/// When built from a [`Vec`] containing duplicate keys, the last value for a
+/// key wins while the key keeps the position of its first occurrence. Use
+/// [`Properties::with_duplicates`] to also keep the earlier values.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let props = Properties::from(parse(b"a=1\nb=2\na=3").unwrap());
+/// assert_eq!(Some("3"), props.get("a"));
+/// assert_eq!(2, props.len());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Properties {
+    props: Vec<Property>,
+    index: HashMap<String, usize>,
+    /// The prefix, including its trailing `.`, prepended to keys on lookup
+    base: Option<String>,
+    /// Every value, in order, of the keys which were retained more than once
+    duplicates: HashMap<usize, Vec<String>>,
+}
+
+impl Properties {
+    /// Creates an empty set of properties
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a set of properties which retains every value of a duplicated
+    /// key, in order, for use with [`Properties::get_all`]. Other lookups
+    /// return the last value.
+    ///
+    /// ```
+    /// use props_rs::*;
+    ///
+    /// let props = Properties::with_duplicates(parse(b"a=1\na=2").unwrap());
+    /// assert_eq!(["1", "2"], props.get_all("a"));
+    /// assert_eq!(Some("2"), props.get("a"));
+    /// ```
+    pub fn with_duplicates(props: Vec<Property>) -> Self {
+        let mut set = Properties::new();
+        for prop in props.into_iter() {
+            match set.index.get(&prop.key) {
+                Some(&i) => {
+                    let last = std::mem::replace(&mut set.props[i].value, prop.value.clone());
+                    set.duplicates
+                        .entry(i)
+                        .or_insert_with(|| vec![last])
+                        .push(prop.value);
+                }
+                None => {
+                    set.insert_absolute(prop.key, prop.value);
+                }
+            }
+        }
+        set
+    }
+
+    /// Returns the number of unique keys
+    pub fn len(&self) -> usize {
+        self.props.len()
+    }
+
+    /// Returns whether there are no properties
+    pub fn is_empty(&self) -> bool {
+        self.props.is_empty()
+    }
+
+    /// Sets a base prefix which is prepended, followed by a `.`, to the key of
+    /// every lookup. With a base of `myapp`, `get("db.host")` returns the value
+    /// of `myapp.db.host`.
+    ///
+    /// The base applies to every method taking a key, including
+    /// [`Properties::insert`] and [`Properties::entry`]. Use
+    /// [`Properties::get_absolute`] to look up a key outside of the base.
+    ///
+    /// ```
+    /// use props_rs::*;
+    ///
+    /// let props = Properties::from(parse(b"myapp.db.host=localhost").unwrap())
+    ///     .with_base("myapp");
+    /// assert_eq!(Some("localhost"), props.get("db.host"));
+    /// ```
+    pub fn with_base(mut self, base: &str) -> Self {
+        let base = base.trim_end_matches('.');
+        self.base = if base.is_empty() {
+            None
+        } else {
+            Some(format!("{}.", base))
+        };
+        self
+    }
+
+    /// Returns the full key looked up for `key`, after applying the base
+    fn resolve<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        match &self.base {
+            Some(base) => Cow::Owned(format!("{}{}", base, key)),
+            None => Cow::Borrowed(key),
+        }
+    }
+
+    /// Returns the value for a key, if it is present
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_absolute(&self.resolve(key))
+    }
+
+    /// Returns the value for a key, if it is present, ignoring any base set
+    /// by [`Properties::with_base`]
+    pub fn get_absolute(&self, key: &str) -> Option<&str> {
+        self.index.get(key).map(|&i| self.props[i].value.as_str())
+    }
+
+    /// Returns every value for a key in the order they were given, or an empty
+    /// slice if the key is not present. Only sets created with
+    /// [`Properties::with_duplicates`] hold more than one value per key.
+    pub fn get_all(&self, key: &str) -> &[String] {
+        match self.index.get(self.resolve(key).as_ref()) {
+            Some(i) => match self.duplicates.get(i) {
+                Some(values) => values,
+                None => std::slice::from_ref(&self.props[*i].value),
+            },
+            None => &[],
+        }
+    }
+
+    /// Returns whether a key is present
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(self.resolve(key).as_ref())
+    }
+
+    /// Returns the value for a key, or an error naming the key if it is not
+    /// present.
+    pub fn expect(&self, key: &str) -> Result<&str, MissingKeyError> {
+        let key = self.resolve(key);
+        self.get_absolute(&key).ok_or_else(|| MissingKeyError {
+            key: key.into_owned(),
+        })
+    }
+
+    /// Returns the value for a key split on `delim`, if it is present. Items
+    /// have surrounding whitespace removed and empty items are skipped, so
+    /// `a, b,,c` gives `["a", "b", "c"]`.
+    ///
+    /// ```
+    /// use props_rs::*;
+    ///
+    /// let props = Properties::from(parse(b"hosts=a.local, b.local").unwrap());
+    /// assert_eq!(Some(vec![String::from("a.local"), String::from("b.local")]),
+    ///     props.get_list("hosts", ','));
+    /// ```
+    pub fn get_list(&self, key: &str, delim: char) -> Option<Vec<String>> {
+        self.get(key).map(|value| {
+            value
+                .split(delim)
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(String::from)
+                .collect()
+        })
+    }
+
+    /// Returns the value for a key split on `delim` like
+    /// [`Properties::get_list`], or an error naming the key if it is not
+    /// present or holds no items.
+    ///
+    /// ```
+    /// use props_rs::*;
+    ///
+    /// let props = Properties::from(parse(b"hosts= , ").unwrap());
+    /// let err = props.get_required_list("hosts", ',').unwrap_err();
+    /// assert_eq!("required list `hosts` is empty", err.to_string());
+    /// ```
+    pub fn get_required_list(&self, key: &str, delim: char) -> Result<Vec<String>, ConfigError> {
+        let list = self.get_list(key, delim).ok_or_else(|| MissingKeyError {
+            key: self.resolve(key).into_owned(),
+        })?;
+        if list.is_empty() {
+            Err(ConfigError::EmptyList {
+                key: self.resolve(key).into_owned(),
+            })
+        } else {
+            Ok(list)
+        }
+    }
+
+    /// Maps the value for a key to one of `variants`, if the key is present.
+    /// The value must equal the name of a variant after surrounding whitespace
+    /// is removed, otherwise an error naming the key and every allowed name is
+    /// returned.
+    ///
+    /// ```
+    /// use props_rs::*;
+    ///
+    /// #[derive(Clone, Debug, PartialEq)]
+    /// enum Level {
+    ///     Debug,
+    ///     Info,
+    /// }
+    ///
+    /// let levels = [("debug", Level::Debug), ("info", Level::Info)];
+    /// let props = Properties::from(parse(b"level=info\nother=trace").unwrap());
+    /// assert_eq!(Some(Ok(Level::Info)), props.get_enum("level", &levels));
+    /// assert_eq!(None, props.get_enum("missing", &levels));
+    /// let err = props.get_enum("other", &levels).unwrap().unwrap_err();
+    /// assert_eq!("key `other`: `trace` is not one of `debug`, `info`", err.to_string());
+    /// ```
+    pub fn get_enum<T: Clone>(
+        &self,
+        key: &str,
+        variants: &[(&str, T)],
+    ) -> Option<Result<T, InvalidEnumError>> {
+        let value = self.get(key)?;
+        let found = variants.iter().find(|(name, _)| *name == value.trim());
+        Some(match found {
+            Some((_, variant)) => Ok(variant.clone()),
+            None => Err(InvalidEnumError {
+                key: self.resolve(key).into_owned(),
+                value: value.to_string(),
+                allowed: variants.iter().map(|(name, _)| name.to_string()).collect(),
+            }),
+        })
+    }
+
+    /// Returns an iterator over the properties, in order
+    pub fn iter(&self) -> impl Iterator<Item = &Property> {
+        self.props.iter()
+    }
+
+    /// Sets the value for a key, returning the previous value if there was
+    /// one. An existing key keeps its position while a new key is appended.
+    ///
+    /// Any other values retained for the key are discarded. Like
+    /// [`Properties::get`], the key is prefixed with the base set with
+    /// [`Properties::with_base`].
+    pub fn insert(&mut self, key: String, value: String) -> Option<String> {
+        let key = match self.resolve(&key) {
+            Cow::Owned(resolved) => resolved,
+            Cow::Borrowed(_) => key,
+        };
+        self.insert_absolute(key, value)
+    }
+
+    /// Sets the value for a full key, ignoring any base
+    fn insert_absolute(&mut self, key: String, value: String) -> Option<String> {
+        match self.index.get(&key) {
+            Some(&i) => {
+                self.duplicates.remove(&i);
+                Some(std::mem::replace(&mut self.props[i].value, value))
+            }
+            None => {
+                self.index.insert(key.clone(), self.props.len());
+                self.props.push(Property { key, value });
+                None
+            }
+        }
+    }
+
+    /// Returns the entry for a key, for inserting a value only if the key is
+    /// not present. Like [`Properties::get`], the key is prefixed with the base
+    /// set with [`Properties::with_base`], so a vacant entry inserts the
+    /// prefixed key.
+    ///
+    /// ```
+    /// use props_rs::*;
+    ///
+    /// let mut props = Properties::from(parse(b"host=example.com").unwrap());
+    /// props.entry("host").or_insert("localhost");
+    /// props.entry("port").or_insert("8080");
+    /// assert_eq!(Some("example.com"), props.get("host"));
+    /// assert_eq!(Some("8080"), props.get("port"));
+    /// ```
+    pub fn entry(&mut self, key: &str) -> PropertyEntry<'_> {
+        let key = self.resolve(key).into_owned();
+        match self.index.get(&key) {
+            Some(&index) => PropertyEntry::Occupied(OccupiedEntry { props: self, index }),
+            None => PropertyEntry::Vacant(VacantEntry { props: self, key }),
+        }
+    }
+
+    /// Applies `other` on top of these properties. Values in `other` override
+    /// existing ones in place, and keys only in `other` are appended in the
+    /// order they appear there. Keys in `other` are full keys, so no base is
+    /// applied to them.
+    ///
+    /// ```
+    /// use props_rs::*;
+    ///
+    /// let mut props = Properties::from(parse(b"a=1\nb=2").unwrap());
+    /// props.merge_into(Properties::from(parse(b"c=3\na=4").unwrap()));
+    /// let keys: Vec<&str> = props.iter().map(|p| p.key.as_str()).collect();
+    /// assert_eq!(vec!["a", "b", "c"], keys);
+    /// assert_eq!(Some("4"), props.get("a"));
+    /// ```
+    pub fn merge_into(&mut self, other: Properties) {
+        for prop in other.props.into_iter() {
+            self.insert_absolute(prop.key, prop.value);
+        }
+    }
+}
+
+/// An entry of a key in [`Properties`], returned by [`Properties::entry`].
+#[derive(Debug)]
+pub enum PropertyEntry<'a> {
+    /// The key is present
+    Occupied(OccupiedEntry<'a>),
+    /// The key is not present
+    Vacant(VacantEntry<'a>),
+}
+
+impl<'a> PropertyEntry<'a> {
+    /// Returns the key of the entry
+    pub fn key(&self) -> &str {
+        match self {
+            PropertyEntry::Occupied(e) => e.key(),
+            PropertyEntry::Vacant(e) => e.key(),
+        }
+    }
+
+    /// Appends the key with `default` as its value if it is not present,
+    /// returning its value
+    pub fn or_insert(self, default: impl Into<String>) -> &'a str {
+        self.or_insert_with(|| default.into())
+    }
+
+    /// Appends the key with the value returned by `default` if it is not
+    /// present, returning its value. `default` is only called for a vacant entry
+    pub fn or_insert_with<F: FnOnce() -> String>(self, default: F) -> &'a str {
+        match self {
+            PropertyEntry::Occupied(e) => e.into_value(),
+            PropertyEntry::Vacant(e) => e.insert(default()),
+        }
+    }
+}
+
+/// An entry of a key which is present in [`Properties`].
+#[derive(Debug)]
+pub struct OccupiedEntry<'a> {
+    props: &'a mut Properties,
+    index: usize,
+}
+
+impl<'a> OccupiedEntry<'a> {
+    /// Returns the key of the entry
+    pub fn key(&self) -> &str {
+        &self.props.props[self.index].key
+    }
+
+    /// Returns the value of the entry
+    pub fn get(&self) -> &str {
+        &self.props.props[self.index].value
+    }
+
+    /// Sets the value of the entry, returning the previous value. Any other
+    /// values retained for the key are discarded, as in [`Properties::insert`]
+    pub fn insert(&mut self, value: String) -> String {
+        self.props.duplicates.remove(&self.index);
+        std::mem::replace(&mut self.props.props[self.index].value, value)
+    }
+
+    /// Returns the value of the entry, borrowed for the lifetime of the entry
+    fn into_value(self) -> &'a str {
+        &self.props.props[self.index].value
+    }
+}
+
+/// An entry of a key which is not present in [`Properties`].
+#[derive(Debug)]
+pub struct VacantEntry<'a> {
+    props: &'a mut Properties,
+    key: String,
+}
+
+impl<'a> VacantEntry<'a> {
+    /// Returns the key of the entry
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Appends the key with the given value, returning the value
+    pub fn insert(self, value: String) -> &'a str {
+        let index = self.props.props.len();
+        self.props.insert_absolute(self.key, value);
+        &self.props.props[index].value
+    }
+}
+
+impl From<Vec<Property>> for Properties {
+    fn from(props: Vec<Property>) -> Self {
+        let mut set = Properties::new();
+        for prop in props.into_iter() {
+            set.insert_absolute(prop.key, prop.value);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_from_vec_keeps_order() {
+        let props = Properties::from(parse(b"b=1\na=2\nb=3").unwrap());
+        let keys: Vec<&str> = props.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(vec!["b", "a"], keys);
+        assert_eq!(Some("3"), props.get("b"));
+        assert!(props.contains_key("a"));
+        assert!(!props.contains_key("c"));
+    }
+
+    #[test]
+    fn test_expect() {
+        let props = Properties::from(parse(b"db.host=localhost").unwrap());
+        assert_eq!(Ok("localhost"), props.expect("db.host"));
+        let err = props.expect("db.port").unwrap_err();
+        assert_eq!("db.port", err.key);
+        assert_eq!("missing required key `db.port`", err.to_string());
+    }
+
+    #[test]
+    fn test_get_enum() {
+        #[derive(Clone, Debug, PartialEq)]
+        enum Mode {
+            Fast,
+            Safe,
+        }
+        let modes = [("fast", Mode::Fast), ("safe", Mode::Safe)];
+        let props =
+            Properties::from(parse(b"db.mode = safe \ndb.other=Fast").unwrap()).with_base("db");
+        assert_eq!(Some(Ok(Mode::Safe)), props.get_enum("mode", &modes));
+
+        let err = props.get_enum("other", &modes).unwrap().unwrap_err();
+        assert_eq!(
+            InvalidEnumError {
+                key: String::from("db.other"),
+                value: String::from("Fast"),
+                allowed: vec![String::from("fast"), String::from("safe")],
+            },
+            err
+        );
+        assert_eq!(
+            "key `db.other`: `Fast` is not one of `fast`, `safe`",
+            err.to_string()
+        );
+        assert_eq!(None, props.get_enum("missing", &modes));
+    }
+
+    #[test]
+    fn test_get_required_list() {
+        let props = Properties::from(
+            parse(
+                b"app.hosts=a, b,,c 
+app.empty= ;  ",
+            )
+            .unwrap(),
+        )
+        .with_base("app");
+        assert_eq!(
+            Ok(vec![
+                String::from("a"),
+                String::from("b"),
+                String::from("c")
+            ]),
+            props.get_required_list("hosts", ',')
+        );
+
+        let err = props.get_required_list("ports", ',').unwrap_err();
+        assert_eq!(
+            ConfigError::Missing(MissingKeyError {
+                key: String::from("app.ports")
+            }),
+            err
+        );
+        assert_eq!("missing required key `app.ports`", err.to_string());
+
+        let err = props.get_required_list("empty", ';').unwrap_err();
+        assert_eq!(
+            ConfigError::EmptyList {
+                key: String::from("app.empty")
+            },
+            err
+        );
+        assert_eq!("required list `app.empty` is empty", err.to_string());
+        assert_eq!(Some(vec![]), props.get_list("empty", ';'));
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut props = Properties::from(parse(b"a=1").unwrap());
+        assert_eq!(
+            Some(String::from("1")),
+            props.insert("a".into(), "2".into())
+        );
+        assert_eq!(None, props.insert("b".into(), "3".into()));
+        assert_eq!(Some("2"), props.get("a"));
+        assert_eq!(2, props.len());
+    }
+
+    #[test]
+    fn test_merge_into() {
+        let mut base =
+            Properties::from(parse(b"db.host=localhost\ndb.port=5432\nname=app").unwrap());
+        let overrides = Properties::from(parse(b"log=debug\ndb.port=6543\nname=prod").unwrap());
+        base.merge_into(overrides);
+        let merged: Vec<(&str, &str)> = base
+            .iter()
+            .map(|p| (p.key.as_str(), p.value.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("db.host", "localhost"),
+                ("db.port", "6543"),
+                ("name", "prod"),
+                ("log", "debug"),
+            ],
+            merged
+        );
+    }
+
+    #[test]
+    fn test_with_base() {
+        let input = b"myapp.db.host=localhost\nmyapp.db.port=5432\nother.key=x";
+        let props = Properties::from(parse(input).unwrap()).with_base("myapp");
+        assert_eq!(Some("localhost"), props.get("db.host"));
+        assert!(props.contains_key("db.port"));
+        assert!(!props.contains_key("myapp.db.port"));
+        assert_eq!(None, props.get("other.key"));
+        assert_eq!("myapp.db.user", props.expect("db.user").unwrap_err().key);
+        // absolute lookups opt out of the base
+        assert_eq!(Some("x"), props.get_absolute("other.key"));
+        assert_eq!(Some("5432"), props.get_absolute("myapp.db.port"));
+    }
+
+    #[test]
+    fn test_get_all() {
+        let input = b"hosts=a\nport=1\nhosts=b\nhosts=c";
+        let props = Properties::with_duplicates(parse(input).unwrap());
+        assert_eq!(["a", "b", "c"], props.get_all("hosts"));
+        assert_eq!(Some("c"), props.get("hosts"));
+        assert_eq!(["1"], props.get_all("port"));
+        assert!(props.get_all("missing").is_empty());
+        assert_eq!(2, props.len());
+
+        // without retaining duplicates only the last value is kept
+        let props = Properties::from(parse(input).unwrap());
+        assert_eq!(["c"], props.get_all("hosts"));
+
+        // setting a value replaces every retained value
+        let mut props = Properties::with_duplicates(parse(input).unwrap());
+        props.insert("hosts".into(), "d".into());
+        assert_eq!(["d"], props.get_all("hosts"));
+    }
+
+    #[test]
+    fn test_entry_vacant() {
+        let mut props = Properties::from(parse(b"a=1").unwrap());
+        assert_eq!("2", props.entry("b").or_insert("2"));
+        assert_eq!("3", props.entry("c").or_insert_with(|| String::from("3")));
+        let keys: Vec<&str> = props.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(vec!["a", "b", "c"], keys);
+        match props.entry("d") {
+            PropertyEntry::Vacant(e) => {
+                assert_eq!("d", e.key());
+                assert_eq!("4", e.insert(String::from("4")));
+            }
+            e => panic!("expected a vacant entry, got {:?}", e),
+        }
+        assert_eq!(Some("4"), props.get("d"));
+    }
+
+    #[test]
+    fn test_entry_occupied() {
+        let input = b"a=1\nb=2\na=3";
+        let mut props = Properties::with_duplicates(parse(input).unwrap());
+        assert_eq!("3", props.entry("a").or_insert("default"));
+        props
+            .entry("b")
+            .or_insert_with(|| panic!("called for an occupied entry"));
+        assert_eq!(2, props.len());
+        assert_eq!(["1", "3"], props.get_all("a"));
+
+        match props.entry("a") {
+            PropertyEntry::Occupied(mut e) => {
+                assert_eq!("3", e.get());
+                assert_eq!("3", e.insert(String::from("4")));
+            }
+            e => panic!("expected an occupied entry, got {:?}", e),
+        }
+        assert_eq!(["4"], props.get_all("a"));
+    }
+
+    #[test]
+    fn test_entry_with_base() {
+        let mut props = Properties::from(parse(b"app.port=1").unwrap()).with_base("app");
+        assert_eq!("1", props.entry("port").or_insert("8080"));
+        assert_eq!("localhost", props.entry("host").or_insert("localhost"));
+        assert_eq!(Some("1"), props.get("port"));
+        assert_eq!(Some("localhost"), props.get("host"));
+        let keys: Vec<&str> = props.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(vec!["app.port", "app.host"], keys);
+    }
+
+    #[test]
+    fn test_insert_and_entry_with_base() {
+        let mut props = Properties::from(parse(b"app.port=1\nport=2").unwrap()).with_base("app");
+        assert_eq!(
+            Some(String::from("1")),
+            props.insert("port".into(), "3".into())
+        );
+        assert_eq!(None, props.insert("host".into(), "localhost".into()));
+        assert_eq!("localhost", props.entry("host").or_insert("example.com"));
+        assert_eq!("3", props.entry("port").or_insert("8080"));
+        assert_eq!(Some("2"), props.get_absolute("port"));
+        let keys: Vec<&str> = props.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(vec!["app.port", "port", "app.host"], keys);
+    }
+}
@@ -0,0 +1,212 @@
+//! Expansion of `${key}` references within property values
+use crate::Property;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Which definition of a referenced key is used when it is defined both in
+/// the properties being resolved and in the resolver's context.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Precedence {
+    /// Keys defined in the properties win over the context
+    #[default]
+    FileFirst,
+    /// Keys defined in the context win over the properties
+    ContextFirst,
+}
+
+/// An error returned when references within values can not be expanded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResolveError {
+    /// A value referenced a key defined in neither the properties nor the context
+    Undefined {
+        /// The key whose value holds the reference
+        key: String,
+        /// The key which was referenced
+        reference: String,
+    },
+    /// A key's value refers back to itself, directly or through other keys
+    Cycle {
+        /// The key whose expansion refers back to itself
+        key: String,
+    },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::Undefined { key, reference } => {
+                write!(f, "key `{}` references undefined key `{}`", key, reference)
+            }
+            ResolveError::Cycle { key } => write!(f, "key `{}` references itself", key),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Expands `${key}` references within property values, using other properties
+/// and an optional context of externally supplied values.
+///
+/// Values from the context are used as they are, while values from the
+/// properties have their own references expanded. A `${` without a closing
+/// `}` is left as it is.
+///
+/// ```
+/// use props_rs::*;
+/// use std::collections::HashMap;
+///
+/// let props = parse(b"host=localhost\nurl=http://${host}:${port}").unwrap();
+/// let mut context = HashMap::new();
+/// context.insert(String::from("port"), String::from("8080"));
+/// let resolved = Resolver::new().context(context).resolve(&props).unwrap();
+/// assert_eq!("http://localhost:8080", resolved[1].value);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Resolver {
+    context: HashMap<String, String>,
+    precedence: Precedence,
+}
+
+impl Resolver {
+    /// Creates a resolver with an empty context which gives the properties
+    /// precedence
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the externally supplied values which references may expand to
+    pub fn context(mut self, context: HashMap<String, String>) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Sets which definition wins when a referenced key is defined both in the
+    /// properties and in the context. Defaults to [`Precedence::FileFirst`]
+    pub fn precedence(mut self, precedence: Precedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Returns the properties with every reference in their values expanded.
+    /// When a key is duplicated, references to it expand to its last value.
+    pub fn resolve(&self, props: &[Property]) -> Result<Vec<Property>, ResolveError> {
+        let file: HashMap<&str, &str> = props
+            .iter()
+            .map(|p| (p.key.as_str(), p.value.as_str()))
+            .collect();
+        let mut stack = Vec::new();
+        props
+            .iter()
+            .map(|p| {
+                stack.push(p.key.as_str());
+                let value = self.expand(&p.key, &p.value, &file, &mut stack)?;
+                stack.pop();
+                Ok(Property {
+                    key: p.key.clone(),
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    /// Expands the references in the value of `key`. `stack` holds the keys
+    /// currently being expanded, to detect cycles.
+    fn expand<'a>(
+        &self,
+        key: &str,
+        value: &str,
+        file: &HashMap<&'a str, &'a str>,
+        stack: &mut Vec<&'a str>,
+    ) -> Result<String, ResolveError> {
+        let mut out = String::with_capacity(value.len());
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            let end = match rest[start + 2..].find('}') {
+                Some(end) => start + 2 + end,
+                None => break,
+            };
+            out.push_str(&rest[..start]);
+            let reference = &rest[start + 2..end];
+            let from_file = file.get_key_value(reference);
+            let from_context = self.context.get(reference);
+            match (self.precedence, from_file, from_context) {
+                (Precedence::ContextFirst, _, Some(v)) | (Precedence::FileFirst, None, Some(v)) => {
+                    out.push_str(v)
+                }
+                (_, Some((&reference, &v)), _) => {
+                    if stack.contains(&reference) {
+                        return Err(ResolveError::Cycle {
+                            key: key.to_string(),
+                        });
+                    }
+                    stack.push(reference);
+                    out.push_str(&self.expand(reference, v, file, stack)?);
+                    stack.pop();
+                }
+                (_, None, None) => {
+                    return Err(ResolveError::Undefined {
+                        key: key.to_string(),
+                        reference: reference.to_string(),
+                    })
+                }
+            }
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn context() -> HashMap<String, String> {
+        let mut context = HashMap::new();
+        context.insert(String::from("env"), String::from("prod"));
+        context
+    }
+
+    #[test]
+    fn test_resolve() {
+        let props = parse(b"a=${b}-${c}\nb=x${c}\nc=y\nd=${unterminated").unwrap();
+        let resolved = Resolver::new().resolve(&props).unwrap();
+        let values: Vec<&str> = resolved.iter().map(|p| p.value.as_str()).collect();
+        assert_eq!(vec!["xy-y", "xy", "y", "${unterminated"], values);
+    }
+
+    #[test]
+    fn test_precedence() {
+        let props = parse(b"env=dev\npath=/srv/${env}").unwrap();
+        let file_first = Resolver::new().context(context()).resolve(&props).unwrap();
+        assert_eq!("/srv/dev", file_first[1].value);
+        let context_first = Resolver::new()
+            .context(context())
+            .precedence(Precedence::ContextFirst)
+            .resolve(&props)
+            .unwrap();
+        assert_eq!("/srv/prod", context_first[1].value);
+        // the key itself keeps its value from the file
+        assert_eq!("dev", context_first[0].value);
+    }
+
+    #[test]
+    fn test_errors() {
+        let props = parse(b"a=${missing}").unwrap();
+        assert_eq!(
+            Err(ResolveError::Undefined {
+                key: String::from("a"),
+                reference: String::from("missing"),
+            }),
+            Resolver::new().resolve(&props)
+        );
+        let props = parse(b"a=${b}\nb=${a}").unwrap();
+        assert_eq!(
+            Err(ResolveError::Cycle {
+                key: String::from("b"),
+            }),
+            Resolver::new().resolve(&props)
+        );
+    }
+}
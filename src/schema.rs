@@ -0,0 +1,257 @@
+//! Validation of parsed properties against a declared schema
+use crate::{parse, ConfigError, Properties, Property};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A constraint which the value of a declared key must satisfy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Constraint {
+    /// The key must be present, any value is accepted.
+    String,
+    /// The key must be present and its value must parse as an [`i64`] which
+    /// falls within the inclusive range `min..=max`.
+    Int {
+        /// The smallest allowed value, or `None` for no lower bound
+        min: Option<i64>,
+        /// The largest allowed value, or `None` for no upper bound
+        max: Option<i64>,
+    },
+}
+
+/// A set of keys and the constraints their values must satisfy.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let schema = Schema::new()
+///     .key("host", Constraint::String)
+///     .key("port", Constraint::Int { min: Some(1), max: Some(65535) });
+/// let parsed = parse(b"host=localhost\nport=8080").unwrap();
+/// assert!(validate(&parsed, &schema).is_ok());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Schema {
+    keys: Vec<(String, Constraint)>,
+}
+
+impl Schema {
+    /// Creates an empty schema which accepts any set of properties
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a key which must satisfy the given constraint
+    pub fn key(mut self, key: &str, constraint: Constraint) -> Self {
+        self.keys.push((key.to_string(), constraint));
+        self
+    }
+}
+
+/// The reason a property failed validation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    /// A declared key was not present
+    Missing,
+    /// The value could not be parsed as an integer
+    NotAnInteger,
+    /// The value was lower than the declared minimum
+    BelowMin(i64),
+    /// The value was higher than the declared maximum
+    AboveMax(i64),
+}
+
+/// An error describing a single property which does not satisfy its schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    /// The key which failed validation
+    pub key: String,
+    /// The value of the key, if it was present
+    pub value: Option<String>,
+    /// The reason validation failed
+    pub kind: ValidationErrorKind,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.value.as_deref().unwrap_or_default();
+        match self.kind {
+            ValidationErrorKind::Missing => write!(f, "missing required key `{}`", self.key),
+            ValidationErrorKind::NotAnInteger => {
+                write!(f, "key `{}`: `{}` is not an integer", self.key, value)
+            }
+            ValidationErrorKind::BelowMin(min) => write!(
+                f,
+                "key `{}`: {} is below the minimum of {}",
+                self.key, value, min
+            ),
+            ValidationErrorKind::AboveMax(max) => write!(
+                f,
+                "key `{}`: {} is above the maximum of {}",
+                self.key, value, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Checks a set of properties against a [`Schema`].
+///
+/// When a key is duplicated, the last value is the one which is validated.
+/// All errors are accumulated rather than stopping at the first failure.
+pub fn validate(props: &[Property], schema: &Schema) -> Result<(), Vec<ValidationError>> {
+    let values: HashMap<&str, &str> = props
+        .iter()
+        .map(|p| (p.key.as_str(), p.value.as_str()))
+        .collect();
+    let errors: Vec<ValidationError> = schema
+        .keys
+        .iter()
+        .filter_map(|(key, constraint)| check(key, values.get(key.as_str()).copied(), constraint))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Parses a properties file and checks it against a [`Schema`], so that a
+/// configuration can be loaded with a single `?`.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let schema = Schema::new().key("port", Constraint::Int { min: Some(1), max: None });
+/// let props = parse_and_validate(b"port=8080", &schema).unwrap();
+/// assert_eq!(Some("8080"), props.get("port"));
+///
+/// let err = parse_and_validate(b"port=0", &schema).unwrap_err();
+/// assert_eq!("key `port`: 0 is below the minimum of 1", err.to_string());
+/// ```
+pub fn parse_and_validate(input: &[u8], spec: &Schema) -> Result<Properties, ConfigError> {
+    let props = parse(input)?;
+    validate(&props, spec)?;
+    Ok(Properties::from(props))
+}
+
+/// Checks a single value against its constraint, returning an error on failure
+fn check(key: &str, value: Option<&str>, constraint: &Constraint) -> Option<ValidationError> {
+    let error = |kind| {
+        Some(ValidationError {
+            key: key.to_string(),
+            value: value.map(String::from),
+            kind,
+        })
+    };
+    let value = match value {
+        Some(v) => v,
+        None => return error(ValidationErrorKind::Missing),
+    };
+    match *constraint {
+        Constraint::String => None,
+        Constraint::Int { min, max } => match value.trim().parse::<i64>() {
+            Err(_) => error(ValidationErrorKind::NotAnInteger),
+            Ok(v) => match (min, max) {
+                (Some(min), _) if v < min => error(ValidationErrorKind::BelowMin(min)),
+                (_, Some(max)) if v > max => error(ValidationErrorKind::AboveMax(max)),
+                _ => None,
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn port_schema() -> Schema {
+        Schema::new().key(
+            "port",
+            Constraint::Int {
+                min: Some(1024),
+                max: Some(65535),
+            },
+        )
+    }
+
+    #[test]
+    fn test_int_in_range() {
+        let props = parse(b"port=8080").unwrap();
+        assert_eq!(Ok(()), validate(&props, &port_schema()));
+    }
+
+    #[test]
+    fn test_int_below_min() {
+        let props = parse(b"port=80").unwrap();
+        let errors = validate(&props, &port_schema()).unwrap_err();
+        assert_eq!(
+            vec![ValidationError {
+                key: String::from("port"),
+                value: Some(String::from("80")),
+                kind: ValidationErrorKind::BelowMin(1024),
+            }],
+            errors
+        );
+        assert_eq!(
+            "key `port`: 80 is below the minimum of 1024",
+            errors[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_int_not_numeric() {
+        let props = parse(b"port=http").unwrap();
+        let errors = validate(&props, &port_schema()).unwrap_err();
+        assert_eq!(1, errors.len());
+        assert_eq!(ValidationErrorKind::NotAnInteger, errors[0].kind);
+        assert_eq!(Some(String::from("http")), errors[0].value);
+    }
+
+    #[test]
+    fn test_errors_accumulate() {
+        let schema = port_schema().key("host", Constraint::String).key(
+            "threads",
+            Constraint::Int {
+                min: None,
+                max: Some(8),
+            },
+        );
+        let props = parse(b"port=1\nthreads=16").unwrap();
+        let kinds: Vec<ValidationErrorKind> = validate(&props, &schema)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(
+            vec![
+                ValidationErrorKind::BelowMin(1024),
+                ValidationErrorKind::Missing,
+                ValidationErrorKind::AboveMax(8),
+            ],
+            kinds
+        );
+    }
+
+    #[test]
+    fn test_parse_and_validate() {
+        let props = parse_and_validate(b"port=8080\nport=9090", &port_schema()).unwrap();
+        assert_eq!(Some("9090"), props.get("port"));
+
+        match parse_and_validate(b"port=80\nhost=x", &port_schema()) {
+            Err(ConfigError::Validation(errors)) => {
+                assert_eq!(ValidationErrorKind::BelowMin(1024), errors[0].kind)
+            }
+            r => panic!("expected a validation error, got {:?}", r),
+        }
+
+        assert_eq!(
+            Err(ConfigError::Parse(crate::ParseError::Syntax {
+                line: 2,
+                column: 1
+            })),
+            parse_and_validate(b"port=8080\n=x", &port_schema())
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Parsing of properties read incrementally from a stream
+use crate::line_index::line_ends;
+use crate::parser::{self, Line, ParseOptions};
+use crate::writer::{write_header, write_line};
+use crate::{ParseError, Property, PropsError, WriteOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+
+/// Parses a properties file read from `reader`, like [`parse`](crate::parse).
+///
+/// The reader is buffered internally. See [`parse_buf_read`] for how the
+/// input is read and how errors are reported.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let parsed = parse_reader(&b"key=value\n"[..]).unwrap();
+/// assert_eq!("value", parsed[0].value);
+/// ```
+pub fn parse_reader<R: Read>(reader: R) -> Result<Vec<Property>, PropsError> {
+    parse_buf_read(BufReader::new(reader))
+}
+
+/// Parses a properties file read from `reader`, like [`parse`](crate::parse).
+///
+/// The input is read and parsed one logical line at a time, so only a single
+/// property is held in memory before it is parsed. Error positions are
+/// relative to the start of the stream.
+///
+/// Unlike [`parse`](crate::parse), a stream which ends within a property whose
+/// line continuation expects another line fails with
+/// [`ParseError::UnexpectedEof`], as the rest of the property may not have
+/// arrived yet.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let err = parse_buf_read(&b"a=1\nb=2 \\\n"[..]).unwrap_err();
+/// match err {
+///     PropsError::Parse(ParseError::UnexpectedEof { line, .. }) => assert_eq!(3, line),
+///     e => panic!("expected an unexpected end of input, got {:?}", e),
+/// }
+/// ```
+pub fn parse_buf_read<R: BufRead>(reader: R) -> Result<Vec<Property>, PropsError> {
+    let mut props = Vec::new();
+    for_each_property(reader, |property| {
+        props.push(property);
+        Ok(())
+    })?;
+    Ok(props)
+}
+
+/// Reads properties from `r`, passes each to `f` and writes the properties it
+/// returns to `w` as [`write`](crate::write) does, one at a time. Returning
+/// `None` from `f` drops the property.
+///
+/// Only a single property is held in memory at once, so arbitrarily large
+/// files can be transformed. Comments and blank lines are not written. The
+/// input is read as in [`parse_buf_read`], so a property which is
+/// continued at the end of the input fails with [`ParseError::UnexpectedEof`],
+/// after every earlier property has been written.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let input = b"user=admin\npassword=hunter2\n";
+/// let mut out = Vec::new();
+/// let redact = |mut p: Property| {
+///     if p.key == "password" {
+///         p.value = String::from("***");
+///     }
+///     Some(p)
+/// };
+/// transform(&input[..], &mut out, redact, &WriteOptions::default()).unwrap();
+/// assert_eq!(b"user=admin\npassword=***\n".to_vec(), out);
+/// ```
+pub fn transform<R: Read, W: Write, F: FnMut(Property) -> Option<Property>>(
+    r: R,
+    w: &mut W,
+    mut f: F,
+    opts: &WriteOptions,
+) -> Result<(), PropsError> {
+    write_header(w, opts)?;
+    let mut line = String::new();
+    for_each_property(BufReader::new(r), |property| {
+        if let Some(property) = f(property) {
+            write_line(w, &property, opts, &mut line)?;
+        }
+        Ok(())
+    })
+}
+
+/// Parses properties from `reader` one logical line at a time, passing each to
+/// `f` as soon as it is complete
+fn for_each_property<R, F>(mut reader: R, mut f: F) -> Result<(), PropsError>
+where
+    R: BufRead,
+    F: FnMut(Property) -> Result<(), PropsError>,
+{
+    let mut pending = Vec::new();
+    let mut line = 0;
+    loop {
+        let read = reader.read_until(b'\n', &mut pending)?;
+        let at_eof = read == 0;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        if !at_eof && (!pending.ends_with(b"\n") || continues(&pending)) {
+            continue;
+        }
+        let eof = || ParseError::unexpected_eof(&pending).offset_lines(line);
+        let lines = match parser::lines(&pending, &mut ParseOptions::default()) {
+            Ok(lines) => lines,
+            // a backslash at the very end is not a valid escape on its own
+            Err(_) if at_eof && continues(&pending) => return Err(eof().into()),
+            Err(e) => return Err(e.offset_lines(line).into()),
+        };
+        for l in lines.into_iter() {
+            if let Line::Property(property, _, raw) = l {
+                if at_eof && continues(raw) {
+                    return Err(eof().into());
+                }
+                f(property)?;
+            }
+        }
+        line += line_ends(&pending).count();
+        pending.clear();
+    }
+}
+
+/// Returns whether the source text of a line ends with a line continuation,
+/// ignoring the indent of an empty line it continues on to
+fn continues(raw: &[u8]) -> bool {
+    let trimmed = raw.iter().rposition(|b| !b" \t\x0c".contains(b));
+    let body = match trimmed.map(|i| &raw[..=i]) {
+        Some(t) if t.ends_with(b"\r\n") => &t[..t.len() - 2],
+        Some(t) if t.ends_with(b"\n") || t.ends_with(b"\r") => &t[..t.len() - 1],
+        _ => raw,
+    };
+    body.iter().rev().take_while(|&&b| b == b'\\').count() % 2 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    /// A reader which returns at most one byte per read
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((&b, rest)), Some(out)) => {
+                    *out = b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_parse() {
+        let input =
+            b"# c \\\nnot=continued\n a = 1 \\\n   2\\\r\n3\r\n\nb\\\\\n\\\n\tc:\\u00e9 \\\n  \nd";
+        let expected = parse(input).unwrap();
+        assert_eq!(expected, parse_reader(&input[..]).unwrap());
+        assert_eq!(
+            expected,
+            parse_buf_read(BufReader::with_capacity(1, Trickle(input))).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        for input in [&b"a=1\nb=2 \\"[..], b"a=1\nb=2 \\\n", b"a=1\nb=2 \\\r\n   "] {
+            match parse_reader(Trickle(input)) {
+                Err(PropsError::Parse(ParseError::UnexpectedEof { line, .. })) => {
+                    assert!(line >= 2, "{:?}", line)
+                }
+                r => panic!("expected an unexpected end of input, got {:?}", r),
+            }
+        }
+        match parse_reader(&b"a=1\nb=2 \\\n"[..]) {
+            Err(PropsError::Parse(e)) => {
+                assert_eq!(ParseError::UnexpectedEof { line: 3, column: 1 }, e)
+            }
+            r => panic!("expected an unexpected end of input, got {:?}", r),
+        }
+
+        // an escaped backslash or space is not a continuation
+        assert!(parse_reader(&b"a=1\\\\"[..]).is_ok());
+        assert!(parse_reader(&b"a=1\\ "[..]).is_ok());
+        assert!(parse_reader(&b"# comment \\"[..]).is_ok());
+    }
+
+    #[test]
+    fn test_transform() {
+        let input = b"# secrets below\nsecret.token = abc \\\n    def\napp.name=demo\n\nsecret.key:x\r\napp.mode=fast";
+        let drop_secrets = |p: Property| {
+            if p.key.starts_with("secret.") {
+                None
+            } else {
+                Some(p)
+            }
+        };
+        let mut out = Vec::new();
+        let opts = WriteOptions {
+            header_comment: Some(String::from("redacted")),
+            ..Default::default()
+        };
+        transform(Trickle(input), &mut out, drop_secrets, &opts).unwrap();
+        assert_eq!(b"# redacted\napp.name=demo\napp.mode=fast\n".to_vec(), out);
+
+        // properties before a truncated one are already written
+        let mut out = Vec::new();
+        let err = transform(&b"a=1\nb=2\\"[..], &mut out, Some, &WriteOptions::default());
+        assert!(matches!(
+            err,
+            Err(PropsError::Parse(ParseError::UnexpectedEof { line: 2, .. }))
+        ));
+        assert_eq!(b"a=1\n".to_vec(), out);
+    }
+
+    #[test]
+    fn test_error_position() {
+        let err = parse_reader(&b"a=1\n\nb=2 \\\n  3\n=x\n"[..]).unwrap_err();
+        match err {
+            PropsError::Parse(e) => assert_eq!(ParseError::Syntax { line: 5, column: 1 }, e),
+            e => panic!("expected a syntax error, got {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Size and shape statistics of a properties file
+use crate::parser::{self, Line, ParseOptions};
+use crate::ParseError;
+use std::collections::HashSet;
+
+/// Statistics describing a properties file, as returned by [`summarize`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// The number of properties, counting every occurrence of a duplicated key
+    pub num_properties: usize,
+    /// The number of distinct keys
+    pub num_unique_keys: usize,
+    /// The number of comment lines
+    pub num_comments: usize,
+    /// The size of the input in bytes
+    pub total_bytes: usize,
+    /// The length in characters of the longest value, after unescaping
+    pub max_value_len: usize,
+}
+
+/// Computes a [`Summary`] of a properties file. The whole input is parsed
+/// first, so a syntax error is returned rather than a partial summary.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let summary = summarize(b"# comment\na=1\na=22").unwrap();
+/// assert_eq!(2, summary.num_properties);
+/// assert_eq!(1, summary.num_unique_keys);
+/// assert_eq!(2, summary.max_value_len);
+/// ```
+pub fn summarize(input: &[u8]) -> Result<Summary, ParseError> {
+    let lines = parser::lines(input, &mut ParseOptions::default())?;
+    let mut keys = HashSet::new();
+    let mut summary = Summary {
+        total_bytes: input.len(),
+        ..Default::default()
+    };
+    for line in lines.into_iter() {
+        match line {
+            Line::Blank(_) => {}
+            Line::Comment(_) => summary.num_comments += 1,
+            Line::Property(prop, _, _) => {
+                summary.num_properties += 1;
+                summary.max_value_len = summary.max_value_len.max(prop.value.chars().count());
+                keys.insert(prop.key);
+            }
+        }
+    }
+    summary.num_unique_keys = keys.len();
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let input = b"# header\r\n\r\n  key   :  value \\\r\n   continued\r\n  \n! note\n\xe9=caf\xe9\nkey=again\n#last";
+        assert_eq!(
+            Summary {
+                num_properties: 3,
+                num_unique_keys: 2,
+                num_comments: 3,
+                total_bytes: input.len(),
+                max_value_len: "value continued".len(),
+            },
+            summarize(input).unwrap()
+        );
+        assert_eq!(Summary::default(), summarize(b"").unwrap());
+        assert!(summarize(b"\\").is_err());
+    }
+}
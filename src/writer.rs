@@ -0,0 +1,847 @@
+//! Serialization of properties into the `.properties` format
+use crate::Property;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Options which change how properties are serialized.
+#[derive(Clone, Debug, Default)]
+pub struct WriteOptions {
+    /// A comment written before any properties. Each line of the comment is
+    /// written prefixed with `# `
+    pub header_comment: Option<String>,
+    /// Wrap values which would make a line longer than this many characters
+    /// onto indented continuation lines, breaking after a space where possible
+    pub wrap_at: Option<usize>,
+    /// Write every non-ASCII character as a `\uXXXX` escape, like Java's
+    /// `Properties.store`. Otherwise only characters outside of ISO 8859-1 are
+    /// escaped
+    pub ascii_safe: bool,
+    /// Write the hex digits of `\uXXXX` escapes in uppercase rather than
+    /// lowercase
+    pub uppercase_escapes: bool,
+    /// Write a comment holding the current UTC time after the header comment,
+    /// like Java's `Properties.store`
+    #[cfg(feature = "time")]
+    pub timestamp: bool,
+    /// The format of the timestamp as a [`time` format
+    /// description](https://time-rs.github.io/book/api/format-description.html)
+    /// such as `[year]-[month]-[day]`. ISO 8601 is used when `None`
+    #[cfg(feature = "time")]
+    pub timestamp_format: Option<String>,
+}
+
+/// Returns the current UTC time formatted for the timestamp comment
+#[cfg(feature = "time")]
+fn timestamp(opts: &WriteOptions) -> io::Result<String> {
+    use time::format_description::well_known::Iso8601;
+    let invalid = |e: Box<dyn std::error::Error + Send + Sync>| {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    };
+    let now = time::OffsetDateTime::now_utc();
+    match &opts.timestamp_format {
+        Some(f) => {
+            let format =
+                time::format_description::parse_borrowed::<2>(f).map_err(|e| invalid(e.into()))?;
+            now.format(&format).map_err(|e| invalid(e.into()))
+        }
+        None => now.format(&Iso8601::DEFAULT).map_err(|e| invalid(e.into())),
+    }
+}
+
+/// Appends each line of `comment` to `out` as a `#` comment line. Any of
+/// `"\r\n"`, `"\r"` or `"\n"` end a line, as they do for the parser
+fn write_comment(comment: &str, out: &mut String) {
+    let comment = comment.replace("\r\n", "\n");
+    let comment = comment.strip_suffix(['\r', '\n']).unwrap_or(&comment);
+    if comment.is_empty() {
+        return;
+    }
+    for line in comment.split(['\r', '\n']) {
+        out.push_str("# ");
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+/// Writes the header and timestamp comments requested by the options
+pub(crate) fn write_header<W: Write>(w: &mut W, opts: &WriteOptions) -> io::Result<()> {
+    let mut header = String::new();
+    if let Some(comment) = &opts.header_comment {
+        write_comment(comment, &mut header);
+    }
+    #[cfg(feature = "time")]
+    if opts.timestamp {
+        write_comment(&timestamp(opts)?, &mut header);
+    }
+    w.write_all(&encode(&header, opts))
+}
+
+/// Appends `c` to `out`, escaping it if it is a special character which can
+/// not appear literally in either a key or a value.
+fn escape_char(c: char, out: &mut String) {
+    match c {
+        '\\' => out.push_str(r"\\"),
+        '\t' => out.push_str(r"\t"),
+        '\n' => out.push_str(r"\n"),
+        '\r' => out.push_str(r"\r"),
+        '\u{c}' => out.push_str(r"\f"),
+        _ => out.push(c),
+    }
+}
+
+/// Appends an escaped key to `out` such that parsing it results in the
+/// original key.
+pub(crate) fn escape_key(key: &str, out: &mut String) {
+    for (i, c) in key.chars().enumerate() {
+        match c {
+            // a line beginning with a comment marker is read as a comment
+            '#' | '!' if i == 0 => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' ' | ':' | '=' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => escape_char(c, out),
+        }
+    }
+}
+
+/// Appends an escaped value to `out` such that parsing it results in the
+/// original value.
+pub(crate) fn escape_value(value: &str, out: &mut String) {
+    let mut state = ValueState::default();
+    for c in value.chars() {
+        escape_value_char(c, &mut state, out);
+    }
+}
+
+/// Tracks the characters of a value escaped so far
+#[derive(Default)]
+struct ValueState {
+    /// A character other than a space has been seen
+    past_leading: bool,
+    /// The last character was a space
+    after_space: bool,
+}
+
+/// Appends a single escaped character of a value to `out`
+fn escape_value_char(c: char, state: &mut ValueState, out: &mut String) {
+    let after_space = std::mem::replace(&mut state.after_space, c == ' ');
+    match c {
+        // whitespace before the value is stripped by the parser unless escaped
+        ' ' if !state.past_leading => out.push_str(r"\ "),
+        // a marker after whitespace starts a comment with inline comments
+        '#' | '!' if after_space => {
+            state.past_leading = true;
+            out.push('\\');
+            out.push(c);
+        }
+        _ => {
+            state.past_leading = true;
+            escape_char(c, out);
+        }
+    }
+}
+
+/// Appends a property as `key=value` to `out`, without a line ending
+pub(crate) fn write_property(prop: &Property, out: &mut String) {
+    escape_key(&prop.key, out);
+    out.push('=');
+    escape_value(&prop.value, out);
+}
+
+/// Appends a property like [`write_property`] followed by a trailing comment,
+/// which starts from its marker e.g. `# note`
+pub(crate) fn write_property_with_comment(prop: &Property, comment: &str, out: &mut String) {
+    escape_key(&prop.key, out);
+    out.push('=');
+    let mut state = ValueState::default();
+    let mut chars = prop.value.chars().peekable();
+    while let Some(c) = chars.next() {
+        // whitespace before a trailing comment is stripped unless escaped
+        if c == ' ' && chars.peek().is_none() {
+            out.push_str(r"\ ");
+        } else {
+            escape_value_char(c, &mut state, out);
+        }
+    }
+    out.push(' ');
+    out.push_str(comment);
+}
+
+/// Appends a property like [`write_property`], wrapping the value onto
+/// continuation lines so that lines are at most `width` characters, not
+/// counting the trailing `\`. A line is only broken between escapes, and
+/// hard at the width when a word does not fit. Widths are measured after
+/// [`encode`], which may write a character as a `\uXXXX` escape.
+fn write_wrapped(prop: &Property, width: usize, opts: &WriteOptions, out: &mut String) {
+    let start = out.len();
+    escape_key(&prop.key, out);
+    out.push('=');
+    let mut state = ValueState::default();
+    let tokens: Vec<String> = prop
+        .value
+        .chars()
+        .map(|c| {
+            let mut token = String::new();
+            escape_value_char(c, &mut state, &mut token);
+            token
+        })
+        .collect();
+    let mut col = encoded_len(&out[start..], opts);
+    let mut i = 0;
+    while i < tokens.len() {
+        let mut end = i;
+        let mut after_space = None;
+        while end < tokens.len() && col + encoded_len(&tokens[end], opts) <= width {
+            col += encoded_len(&tokens[end], opts);
+            if tokens[end] == " " {
+                after_space = Some(end + 1);
+            }
+            end += 1;
+        }
+        if end == tokens.len() {
+            tokens[i..].iter().for_each(|t| out.push_str(t));
+            break;
+        }
+        let mut split = after_space.unwrap_or_else(|| end.max(i + 1));
+        // the parser strips whitespace at the start of a continuation line
+        while split < tokens.len() && tokens[split] == " " {
+            split += 1;
+        }
+        tokens[i..split].iter().for_each(|t| out.push_str(t));
+        if split < tokens.len() {
+            out.push_str(CONTINUATION);
+            col = CONTINUATION.len() - 2;
+        }
+        i = split;
+    }
+}
+
+/// Ends a line which is continued on the next, and indents the next line
+const CONTINUATION: &str = "\\\n    ";
+
+/// Writes properties to `w` in the `.properties` format, one `key=value` per
+/// line. Keys and values are escaped so that parsing the output results in
+/// the same properties.
+///
+/// A property with an empty key would not parse back, so it results in an
+/// [`io::ErrorKind::InvalidInput`] error, as does an invalid
+/// [`WriteOptions::timestamp_format`] when the `time` feature is enabled.
+/// Properties before the invalid one have already been written.
+pub fn write<W: Write>(w: &mut W, props: &[Property], opts: &WriteOptions) -> io::Result<()> {
+    write_filtered(w, props, |_| true, opts)
+}
+
+/// Writes the properties for which `keep` returns true to `w`, in order, as
+/// [`write`] does. The header is written even if no properties are kept.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let props = parse(b"db.host=localhost\nlog=debug").unwrap();
+/// let mut out = Vec::new();
+/// write_filtered(&mut out, &props, |p| p.key.starts_with("db."), &WriteOptions::default())
+///     .unwrap();
+/// assert_eq!(b"db.host=localhost\n".to_vec(), out);
+/// ```
+pub fn write_filtered<W: Write, F: Fn(&Property) -> bool>(
+    w: &mut W,
+    props: &[Property],
+    keep: F,
+    opts: &WriteOptions,
+) -> io::Result<()> {
+    write_header(w, opts)?;
+    let mut line = String::new();
+    for prop in props.iter().filter(|p| keep(p)) {
+        write_line(w, prop, opts, &mut line)?;
+    }
+    Ok(())
+}
+
+/// Writes a single property and its line ending to `w`, using `line` as a
+/// buffer for the serialized text. Fails on an empty key, which would not
+/// parse back.
+pub(crate) fn write_line<W: Write>(
+    w: &mut W,
+    prop: &Property,
+    opts: &WriteOptions,
+    line: &mut String,
+) -> io::Result<()> {
+    if prop.key.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a property with an empty key can not be written",
+        ));
+    }
+    line.clear();
+    match opts.wrap_at {
+        Some(width) => write_wrapped(prop, width, opts, line),
+        None => write_property(prop, line),
+    }
+    line.push('\n');
+    w.write_all(&encode(line, opts))
+}
+
+/// Serializes properties into the `.properties` format, as written by [`write`].
+///
+/// ```
+/// use props_rs::*;
+///
+/// let props = parse(br"key\:with\:colons = value").unwrap();
+/// let bytes = to_bytes(&props, &WriteOptions::default());
+/// assert_eq!(b"key\\:with\\:colons=value\n".to_vec(), bytes);
+/// assert_eq!(props, parse(&bytes).unwrap());
+/// ```
+///
+/// # Panics
+///
+/// Panics if a property has an empty key or, when the `time` feature is
+/// enabled, if [`WriteOptions::timestamp_format`] is invalid. Use [`write`] to
+/// handle the error instead.
+pub fn to_bytes(props: &[Property], opts: &WriteOptions) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    // writing to a Vec can only fail on invalid input
+    write(&mut bytes, props, opts).expect("properties can not be written");
+    bytes
+}
+
+/// Returns whether writing `props` with [`to_bytes`] would produce different
+/// bytes than `existing`, e.g. to skip rewriting a file which is up to date.
+///
+/// Panics on an empty key as [`to_bytes`] does. When the `time` feature is
+/// enabled, a timestamp comment is likely to differ on every call, and an
+/// invalid format panics as well.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let props = parse(b"key=value").unwrap();
+/// assert!(!would_change(b"key=value\n", &props, &WriteOptions::default()));
+/// assert!(would_change(b"key = value\n", &props, &WriteOptions::default()));
+/// ```
+pub fn would_change(existing: &[u8], props: &[Property], opts: &WriteOptions) -> bool {
+    to_bytes(props, opts) != existing
+}
+
+/// Serializes properties exactly as Java's `Properties.store` does, so that
+/// files written by either side stay diff-stable.
+///
+/// The output starts with a comment holding the current time in UTC, formatted
+/// like Java's `Date.toString`. Each property follows in the given order as
+/// `key=value` on its own line ending in `\n`. Spaces, `:`, `=`, `#` and `!`
+/// are escaped in keys, a leading space is escaped in values, and control
+/// characters and every character outside of printable ASCII are escaped with
+/// uppercase `\uXXXX` escapes.
+///
+/// ```
+/// use props_rs::*;
+///
+/// let props = parse(b"caf\xe9 = a: b").unwrap();
+/// let bytes = to_bytes_java(&props);
+/// assert!(bytes.starts_with(b"#"));
+/// assert!(bytes.ends_with(b"\ncaf\\u00E9=a\\: b\n"));
+/// ```
+pub fn to_bytes_java(props: &[Property]) -> Vec<u8> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let mut out = format!("#{}\n", java_date(now));
+    for prop in props.iter() {
+        escape_java(&prop.key, true, &mut out);
+        out.push('=');
+        escape_java(&prop.value, false, &mut out);
+        out.push('\n');
+    }
+    let opts = WriteOptions {
+        ascii_safe: true,
+        uppercase_escapes: true,
+        ..Default::default()
+    };
+    encode(&out, &opts)
+}
+
+/// Escapes a key or value as Java's `Properties.store` does, leaving
+/// characters above `~` for [`encode`]
+fn escape_java(text: &str, escape_spaces: bool, out: &mut String) {
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            ' ' if i == 0 || escape_spaces => out.push_str("\\ "),
+            '\\' | ':' | '=' | '#' | '!' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\x0c' => out.push_str("\\f"),
+            _ if c < ' ' => out.push_str(&format!(r"\u{:04X}", c as u32)),
+            _ => out.push(c),
+        }
+    }
+}
+
+/// Formats seconds since the Unix epoch in UTC like Java's `Date.toString`,
+/// e.g. `Fri Feb 13 23:31:30 UTC 2009`
+fn java_date(secs: u64) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let days = secs / 86400;
+    let time = secs % 86400;
+    // convert the day count to a civil date, with years starting in March
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 2 } else { mp - 10 };
+    let year = yoe + era * 400 + u64::from(month < 2);
+    format!(
+        "{} {} {:02} {:02}:{:02}:{:02} UTC {}",
+        DAYS[(days % 7) as usize],
+        MONTHS[month as usize],
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        year
+    )
+}
+
+/// Encodes serialized text as ISO 8859-1 bytes, the encoding the parser reads
+/// input as. Characters outside of ISO 8859-1, or outside of ASCII with
+/// [`WriteOptions::ascii_safe`], are written as `\uXXXX` escapes.
+pub(crate) fn encode(text: &str, opts: &WriteOptions) -> Vec<u8> {
+    let max = max_unescaped(opts);
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        if (c as u32) <= max {
+            bytes.push(c as u8);
+        } else {
+            let mut units = [0u16; 2];
+            for unit in c.encode_utf16(&mut units) {
+                let escape = if opts.uppercase_escapes {
+                    format!(r"\u{:04X}", unit)
+                } else {
+                    format!(r"\u{:04x}", unit)
+                };
+                bytes.extend_from_slice(escape.as_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+/// Returns the number of bytes [`encode`] writes for `text`
+fn encoded_len(text: &str, opts: &WriteOptions) -> usize {
+    let max = max_unescaped(opts);
+    text.chars()
+        .map(|c| {
+            if (c as u32) <= max {
+                1
+            } else {
+                6 * c.len_utf16()
+            }
+        })
+        .sum()
+}
+
+/// Returns the highest character [`encode`] writes without a `\u` escape
+fn max_unescaped(opts: &WriteOptions) -> u32 {
+    if opts.ascii_safe {
+        0x7E
+    } else {
+        0xFF
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn key(k: &str) -> String {
+        let mut out = String::new();
+        escape_key(k, &mut out);
+        out
+    }
+
+    fn value(v: &str) -> String {
+        let mut out = String::new();
+        escape_value(v, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_escape_key() {
+        assert_eq!("simple.key", key("simple.key"));
+        assert_eq!(r"a\ b\:c\=d", key("a b:c=d"));
+        assert_eq!(r"tab\there\\", key("tab\there\\"));
+        assert_eq!(r"\#key#", key("#key#"));
+        assert_eq!(r"\!key!", key("!key!"));
+    }
+
+    #[test]
+    fn test_escape_value() {
+        assert_eq!("a b:c=d", value("a b:c=d"));
+        assert_eq!(r"\ \ padded ", value("  padded "));
+        assert_eq!(r"line\nbreak\r\f", value("line\nbreak\r\u{c}"));
+    }
+
+    #[test]
+    fn test_encode() {
+        let opts = WriteOptions::default();
+        assert_eq!(b"caf\xe9".to_vec(), encode("caf\u{e9}", &opts));
+        assert_eq!(br"\u20ac".to_vec(), encode("\u{20ac}", &opts));
+        assert_eq!(br"\ud83d\ude00".to_vec(), encode("\u{1f600}", &opts));
+        let opts = WriteOptions {
+            ascii_safe: true,
+            uppercase_escapes: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            br"caf\u00E9 \u007F~".to_vec(),
+            encode("caf\u{e9} \u{7f}~", &opts)
+        );
+    }
+
+    fn prop(k: &str, v: &str) -> Property {
+        Property {
+            key: String::from(k),
+            value: String::from(v),
+        }
+    }
+
+    #[test]
+    fn test_comment_marker_key_round_trip() {
+        let props = vec![prop("#key", "value"), prop("!key", "value")];
+        let bytes = to_bytes(&props, &WriteOptions::default());
+        assert_eq!(b"\\#key=value\n\\!key=value\n".to_vec(), bytes);
+        assert_eq!(props, parse(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_empty_key() {
+        let props = vec![prop("a", "1"), prop("", "v"), prop("b", "2")];
+        let mut out = Vec::new();
+        let err = write(&mut out, &props, &WriteOptions::default()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert_eq!(b"a=1\n".to_vec(), out);
+    }
+
+    #[test]
+    #[should_panic(expected = "properties can not be written")]
+    fn test_to_bytes_empty_key() {
+        to_bytes(&[prop("", "v")], &WriteOptions::default());
+    }
+
+    #[test]
+    fn test_would_change() {
+        let opts = WriteOptions {
+            header_comment: Some(String::from("settings")),
+            ..Default::default()
+        };
+        let mut props = vec![prop("host", "localhost"), prop("port", "8080")];
+        let existing = to_bytes(&props, &opts);
+        assert!(!would_change(&existing, &props, &opts));
+        // the options which produced the file matter too
+        assert!(would_change(&existing, &props, &WriteOptions::default()));
+
+        props[1].value = String::from("9090");
+        assert!(would_change(&existing, &props, &opts));
+        props[1].value = String::from("8080");
+        props.pop();
+        assert!(would_change(&existing, &props, &opts));
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let props = vec![prop("a:b", "c")];
+        assert_eq!(
+            b"a\\:b=c\n".to_vec(),
+            to_bytes(&props, &WriteOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_escaped_key_round_trip() {
+        let props = vec![
+            prop("a:b", "c"),
+            prop("a=b", "c"),
+            prop("  leading", "v"),
+            prop(":=: =:=", " : = "),
+            prop("key\\", "value\\"),
+            prop("#hash", "v"),
+            prop("!bang", "v"),
+        ];
+        let bytes = to_bytes(&props, &WriteOptions::default());
+        assert_eq!(props, parse(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_header_comment() {
+        let opts = WriteOptions {
+            header_comment: Some(String::from("generated\nby a test")),
+            ..Default::default()
+        };
+        let props = vec![prop("key", "value")];
+        let bytes = to_bytes(&props, &opts);
+        assert_eq!(b"# generated\n# by a test\nkey=value\n".to_vec(), bytes);
+        assert_eq!(props, parse(&bytes).unwrap());
+
+        // a lone carriage return also ends a comment line
+        let opts = WriteOptions {
+            header_comment: Some(String::from("note\rinjected=yes\r\nend\r")),
+            ..Default::default()
+        };
+        let bytes = to_bytes(&props, &opts);
+        assert_eq!(
+            b"# note\n# injected=yes\n# end\nkey=value\n".to_vec(),
+            bytes
+        );
+        assert_eq!(props, parse(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_write_filtered() {
+        let props = vec![
+            prop("db.host", "localhost"),
+            prop("log.level", "debug"),
+            prop("db.pass word", " secret"),
+            prop("db", "not a db. key"),
+            prop("db.port", "5432"),
+        ];
+        let mut out = Vec::new();
+        write_filtered(
+            &mut out,
+            &props,
+            |p| p.key.starts_with("db."),
+            &WriteOptions::default(),
+        )
+        .unwrap();
+        let kept: Vec<Property> = vec![props[0].clone(), props[2].clone(), props[4].clone()];
+        assert_eq!(kept, parse(&out).unwrap());
+    }
+
+    #[test]
+    fn test_unicode_escape_round_trip() {
+        let input = br"caf\u00e9=na\u00efve \u20ac\ud83d\ude00";
+        let props = parse(input).unwrap();
+        assert_eq!(prop("caf\u{e9}", "na\u{ef}ve \u{20ac}\u{1f600}"), props[0]);
+
+        let opts = WriteOptions {
+            ascii_safe: true,
+            ..Default::default()
+        };
+        let bytes = to_bytes(&props, &opts);
+        assert_eq!([&input[..], b"\n"].concat(), bytes);
+        assert_eq!(props, parse(&bytes).unwrap());
+
+        let opts = WriteOptions {
+            ascii_safe: true,
+            uppercase_escapes: true,
+            ..Default::default()
+        };
+        let bytes = to_bytes(&props, &opts);
+        assert_eq!(
+            br"caf\u00E9=na\u00EFve \u20AC\uD83D\uDE00".to_vec(),
+            bytes[..bytes.len() - 1].to_vec()
+        );
+        assert_eq!(props, parse(&bytes).unwrap());
+
+        // without ascii_safe only characters outside of ISO 8859-1 are escaped
+        let bytes = to_bytes(&props, &WriteOptions::default());
+        assert_eq!(b"caf\xe9=na\xefve \\u20ac\\ud83d\\ude00\n".to_vec(), bytes);
+        assert_eq!(props, parse(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_wrap_at() {
+        let value = "the quick brown fox jumps over the lazy dog and keeps on running \
+                     until the line is long enough to wrap a few times";
+        let props = vec![prop("message", value)];
+        let opts = WriteOptions {
+            wrap_at: Some(40),
+            ..Default::default()
+        };
+        let bytes = to_bytes(&props, &opts);
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines.len() > 2, "{}", text);
+        for line in &lines[..lines.len() - 1] {
+            assert!(line.ends_with(" \\"), "{}", line);
+            assert!(line.len() <= 41, "{}", line);
+        }
+        assert_eq!(props, parse(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_wrap_at_round_trip() {
+        let props = vec![
+            prop("k", "  leading  spaces   between  words  "),
+            prop("k", &"x".repeat(30)),
+            prop("k", "tabs\tand\\slashes\nand\u{e9}scapes everywhere"),
+            prop("a very long key which is wider than the column", "v"),
+        ];
+        for width in 0..20 {
+            let opts = WriteOptions {
+                wrap_at: Some(width),
+                ..Default::default()
+            };
+            let bytes = to_bytes(&props, &opts);
+            assert_eq!(props, parse(&bytes).unwrap(), "{:?}", bytes);
+        }
+        let opts = WriteOptions {
+            wrap_at: Some(12),
+            ..Default::default()
+        };
+        let bytes = to_bytes(&props[1..2], &opts);
+        assert_eq!(
+            b"k=xxxxxxxxxx\\\n    xxxxxxxx\\\n    xxxxxxxx\\\n    xxxx\n".to_vec(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn test_wrap_at_escaped_width() {
+        let props = vec![
+            prop("k", &"\u{e9}".repeat(30)),
+            prop(
+                "caf\u{e9}",
+                "\u{20ac}\u{1f600} na\u{ef}ve words \u{e9}\u{e9}\u{e9}",
+            ),
+        ];
+        for ascii_safe in [false, true] {
+            for width in [14, 20, 40] {
+                let opts = WriteOptions {
+                    wrap_at: Some(width),
+                    ascii_safe,
+                    ..Default::default()
+                };
+                let bytes = to_bytes(&props, &opts);
+                for line in bytes.split(|&b| b == b'\n') {
+                    let line = line.strip_suffix(b"\\").unwrap_or(line);
+                    // spaces are kept before a break rather than starting a
+                    // continuation, and a single escape is never split
+                    let len = line.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+                    assert!(
+                        len <= width.max(4 + 12),
+                        "{:?}",
+                        String::from_utf8_lossy(line)
+                    );
+                }
+                assert_eq!(props, parse(&bytes).unwrap());
+            }
+        }
+        let opts = WriteOptions {
+            wrap_at: Some(20),
+            ascii_safe: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            b"k=\\u00e9\\u00e9\\u00e9\\\n    \\u00e9\\u00e9\n".to_vec(),
+            to_bytes(&[prop("k", &"\u{e9}".repeat(5))], &opts)
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn test_timestamp_comment() {
+        let props = vec![prop("key", "value")];
+        let opts = WriteOptions {
+            header_comment: Some(String::from("header")),
+            timestamp: true,
+            ..Default::default()
+        };
+        let bytes = to_bytes(&props, &opts);
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(3, lines.len());
+        assert_eq!("# header", lines[0]);
+        // e.g. # 2026-01-31T12:00:00.000000000Z
+        assert!(lines[1].starts_with("# 2"), "{}", lines[1]);
+        assert!(lines[1].ends_with('Z'), "{}", lines[1]);
+        assert_eq!(props, parse(&bytes).unwrap());
+
+        let opts = WriteOptions {
+            timestamp: true,
+            timestamp_format: Some(String::from("[year]")),
+            ..Default::default()
+        };
+        let text = String::from_utf8(to_bytes(&props, &opts)).unwrap();
+        assert_eq!(6, text.lines().next().unwrap().len());
+
+        let opts = WriteOptions {
+            timestamp: true,
+            timestamp_format: Some(String::from("[bogus")),
+            ..Default::default()
+        };
+        let err = write(&mut Vec::new(), &props, &opts).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    /// Checks `to_bytes_java` against a fixture written by Java's
+    /// `Properties.store`, skipping the date comment on the first line
+    fn assert_java_fixture(fixture: &[u8], pairs: &[(&str, &str)]) {
+        let props: Vec<Property> = pairs
+            .iter()
+            .map(|&(key, value)| Property {
+                key: String::from(key),
+                value: String::from(value),
+            })
+            .collect();
+        let skip_date = |b: &[u8]| b[b.iter().position(|&c| c == b'\n').unwrap() + 1..].to_vec();
+        let bytes = to_bytes_java(&props);
+        assert_eq!(
+            String::from_utf8(skip_date(fixture)).unwrap(),
+            String::from_utf8(skip_date(&bytes)).unwrap()
+        );
+        assert_eq!(props, parse(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_to_bytes_java() {
+        assert_java_fixture(
+            include_bytes!("../tests/fixtures/java/basic.properties"),
+            &[("host", "localhost"), ("port", "8080"), ("empty", "")],
+        );
+        assert_java_fixture(
+            include_bytes!("../tests/fixtures/java/escapes.properties"),
+            &[
+                ("key with spaces", "  leading and trailing  "),
+                ("a:b=c", "x=y:z"),
+                ("#hash", "!bang # inline"),
+                ("!", "#"),
+                ("back\\slash", "C:\\path\\to"),
+                ("tab\tkey", "ctrl\t\n\r\x0c\u{1}\u{7f}"),
+                ("multi", "line one\nline two"),
+            ],
+        );
+        assert_java_fixture(
+            include_bytes!("../tests/fixtures/java/unicode.properties"),
+            &[
+                ("caf\u{e9}", "na\u{ef}ve \u{a0}nbsp"),
+                ("euro", "\u{20ac}100"),
+                ("emoji", "\u{1f600}"),
+                ("\u{65e5}\u{672c}", "\u{8a9e}"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_java_date() {
+        // as printed by `new Date(millis).toString()` in UTC
+        assert_eq!("Thu Jan 01 00:00:05 UTC 1970", java_date(5));
+        assert_eq!("Tue Feb 29 00:00:00 UTC 2000", java_date(951_782_400));
+        assert_eq!("Fri Feb 13 23:31:30 UTC 2009", java_date(1_234_567_890));
+        let bytes = to_bytes_java(&[]);
+        assert_eq!(30, bytes.len());
+        assert_eq!((&b"#"[..], &b" UTC "[..]), (&bytes[..1], &bytes[20..25]));
+    }
+}
@@ -0,0 +1,68 @@
+// Regenerates the fixtures used to test `to_bytes_java` against
+// `java.util.Properties.store`. Run from this directory with
+//
+//     TZ=UTC java Store.java
+//
+// The first line of every fixture is the date comment, which tests skip.
+import java.io.FileOutputStream;
+import java.io.IOException;
+import java.util.ArrayList;
+import java.util.Collections;
+import java.util.LinkedHashSet;
+import java.util.List;
+import java.util.Map;
+import java.util.Properties;
+import java.util.Set;
+
+public class Store {
+    /** Properties which are stored in insertion order */
+    static class Ordered extends Properties {
+        private final List<Object> order = new ArrayList<>();
+
+        @Override
+        public synchronized Object put(Object key, Object value) {
+            order.remove(key);
+            order.add(key);
+            return super.put(key, value);
+        }
+
+        @Override
+        public Set<Map.Entry<Object, Object>> entrySet() {
+            Set<Map.Entry<Object, Object>> entries = new LinkedHashSet<>();
+            for (Object key : order) {
+                entries.add(Map.entry(key, get(key)));
+            }
+            return Collections.unmodifiableSet(entries);
+        }
+    }
+
+    static void store(String name, String... pairs) throws IOException {
+        Properties props = new Ordered();
+        for (int i = 0; i < pairs.length; i += 2) {
+            props.setProperty(pairs[i], pairs[i + 1]);
+        }
+        try (FileOutputStream out = new FileOutputStream(name + ".properties")) {
+            props.store(out, null);
+        }
+    }
+
+    public static void main(String[] args) throws IOException {
+        store("basic",
+                "host", "localhost",
+                "port", "8080",
+                "empty", "");
+        store("escapes",
+                "key with spaces", "  leading and trailing  ",
+                "a:b=c", "x=y:z",
+                "#hash", "!bang # inline",
+                "!", "#",
+                "back\\slash", "C:\\path\\to",
+                "tab\tkey", "ctrl\t\n\r\f\u0001\u007f",
+                "multi", "line one\nline two");
+        store("unicode",
+                "caf\u00e9", "na\u00efve \u00a0nbsp",
+                "euro", "\u20ac100",
+                "emoji", "\ud83d\ude00",
+                "\u65e5\u672c", "\u8a9e");
+    }
+}